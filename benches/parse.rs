@@ -0,0 +1,65 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use scrapbox_converter::parse_page;
+
+/// A page shaped like a typical export: prose with links, hashtags,
+/// emphasis, URLs and nested lists.
+fn page(lines: usize) -> String {
+    let mut page = String::new();
+    for i in 0..lines {
+        let indent = "\t".repeat(i % 4);
+        page.push_str(&format!(
+            "{}line {} links to [page {}] and #tag{} with [* bold] text, \
+             see [https://example.com/{} example] and `code`\n",
+            indent,
+            i,
+            i % 50,
+            i % 10,
+            i
+        ));
+    }
+    page
+}
+
+/// Parsing borrows unchanged text from the input; `owned` adds the cost of
+/// copying it with `into_owned`.
+///
+/// To compare with the parser from before text was borrowed, copy this file
+/// without `owned`, and the criterion dev-dependency, into a checkout of that
+/// version and run `cargo bench --bench parse -- --save-baseline before
+/// export/borrowed` there, then `cargo bench --bench parse -- --baseline
+/// before export/borrowed` here.
+fn parse(c: &mut Criterion) {
+    let input = page(10_000);
+    let mut group = c.benchmark_group("export");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(20);
+    group.bench_function("borrowed", |b| {
+        b.iter_with_large_drop(|| parse_page(&input).unwrap())
+    });
+    group.bench_function("owned", |b| {
+        b.iter_batched(
+            || (),
+            |_| parse_page(&input).unwrap().into_owned(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// A single line as long as a whole export, which made text scanning
+/// quadratic when every call searched the rest of the input.
+fn long_line(c: &mut Criterion) {
+    let mut group = c.benchmark_group("long_line");
+    group.sample_size(20);
+    for len in [10_000, 100_000] {
+        let input = page(len).replace('\n', " ");
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(len), &input, |b, input| {
+            b.iter_with_large_drop(|| parse_page(input).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, long_line);
+criterion_main!(benches);
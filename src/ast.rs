@@ -1,32 +1,142 @@
+//! Syntax tree of a Scrapbox page.
+//!
+//! With the `serde` feature every type can be (de)serialized. Structs map to
+//! objects with their field names, enums are internally tagged by a snake_case
+//! `"type"` field and `ListKind` is a plain string. This representation is
+//! stable: fields may be added, but existing names will not change.
+//!
+//! ```json
+//! {
+//!   "lines": [
+//!     {
+//!       "kind": { "type": "list", "kind": "disc", "level": 1 },
+//!       "values": [
+//!         { "kind": { "type": "hash_tag", "value": "tag" } },
+//!         { "kind": { "type": "text", "value": " and " } },
+//!         { "kind": { "type": "bracket", "kind": { "type": "internal_link", "title": "Page" } } }
+//!       ]
+//!     }
+//!   ],
+//!   "meta": { "title": "Example", "created": null, "updated": null }
+//! }
+//! ```
+//!
+//! Text in the tree borrows from the parsed input where it can, so parsing
+//! does not allocate for unchanged content. Passes replace nodes with owned
+//! data, and `into_owned` detaches a page from its input.
+
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Page<'a> {
+    pub lines: Vec<Line<'a>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub meta: PageMeta,
+}
+
+/// Information about a page that is not part of its text.
+/// Timestamps are unix seconds.
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Page {
-    pub lines: Vec<Line>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PageMeta {
+    pub title: Option<String>,
+    pub created: Option<i64>,
+    pub updated: Option<i64>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Line {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Line<'a> {
     pub kind: LineKind,
-    pub values: Vec<Syntax>,
+    pub values: Vec<Syntax<'a>>,
 }
 
-impl Line {
-    pub fn new(kind: LineKind, values: Vec<Syntax>) -> Self {
+impl<'a> Line<'a> {
+    pub fn new(kind: LineKind, values: Vec<Syntax<'a>>) -> Self {
         Self { kind, values }
     }
+
+    /// Number of lines this line takes up in Scrapbox text: a code block has
+    /// its `code:` line and one line per line of code.
+    pub fn source_lines(&self) -> usize {
+        match self.code() {
+            None | Some("") => 1,
+            Some(code) => 1 + code.split('\n').count(),
+        }
+    }
+
+    /// The code of a `CodeBlock` line.
+    pub fn code(&self) -> Option<&str> {
+        if !matches!(self.kind, LineKind::CodeBlock(_)) {
+            return None;
+        }
+        match self.values.first().map(|v| &v.kind) {
+            Some(SyntaxKind::Text(text)) => Some(&text.value),
+            _ => Some(""),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 pub enum LineKind {
     Normal,
     List(List),
+    /// A `code:name` block. The line holds the code, without indentation, as
+    /// a single `Text`.
+    CodeBlock(CodeBlock),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodeBlock {
+    /// File name or language after `code:`.
+    pub name: String,
+    /// Indentation of the `code:` line, as for a list item.
+    pub level: usize,
+}
+
+impl CodeBlock {
+    pub fn new(name: impl Into<String>, level: usize) -> Self {
+        Self {
+            name: name.into(),
+            level,
+        }
+    }
+
+    /// Language for syntax highlighting: the extension of a file name, or
+    /// the name itself.
+    pub fn language(&self) -> &str {
+        match self.name.rsplit_once('.') {
+            Some((_, extension)) => extension,
+            None => &self.name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct List {
     pub kind: ListKind,
+    /// Indentation, 1 for a top-level item.
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "list_level"))]
     pub level: usize,
 }
 
+/// Reads `List::level`, which is never 0.
+#[cfg(feature = "serde")]
+fn list_level<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    use serde::Deserialize;
+
+    let level = usize::deserialize(deserializer)?;
+    if level == 0 {
+        return Err(serde::de::Error::custom("list level must be at least 1"));
+    }
+    Ok(level)
+}
+
 impl List {
     pub fn new(kind: ListKind, level: usize) -> Self {
         Self { kind, level }
@@ -55,6 +165,8 @@ impl List {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ListKind {
     Disc,
     Decimal,
@@ -62,172 +174,324 @@ pub enum ListKind {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Syntax {
-    pub kind: SyntaxKind,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Syntax<'a> {
+    pub kind: SyntaxKind<'a>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum SyntaxKind {
-    HashTag(HashTag),
-    Bracket(Bracket),
-    BlockQuote(BlockQuote),
-    Text(Text),
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum SyntaxKind<'a> {
+    HashTag(HashTag<'a>),
+    Bracket(Bracket<'a>),
+    BlockQuote(BlockQuote<'a>),
+    Text(Text<'a>),
 }
 
-impl Syntax {
-    pub fn new(kind: SyntaxKind) -> Self {
+impl<'a> Syntax<'a> {
+    pub fn new(kind: SyntaxKind<'a>) -> Self {
         Self { kind }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct HashTag {
-    pub value: String,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HashTag<'a> {
+    pub value: Cow<'a, str>,
 }
 
-impl HashTag {
-    pub fn new(value: &str) -> Self {
+impl<'a> HashTag<'a> {
+    pub fn new(value: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            value: value.to_string(),
+            value: value.into(),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Bracket {
-    pub kind: BracketKind,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bracket<'a> {
+    pub kind: BracketKind<'a>,
 }
 
-impl Bracket {
-    pub fn new(kind: BracketKind) -> Self {
+impl<'a> Bracket<'a> {
+    pub fn new(kind: BracketKind<'a>) -> Self {
         Self { kind }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum BracketKind {
-    InternalLink(InternalLink),
-    ExternalLink(ExternalLink),
-    Emphasis(Emphasis),
-    Heading(Heading),
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum BracketKind<'a> {
+    InternalLink(InternalLink<'a>),
+    ExternalLink(ExternalLink<'a>),
+    Emphasis(Emphasis<'a>),
+    Heading(Heading<'a>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct BlockQuote {
-    pub value: String,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockQuote<'a> {
+    pub value: Cow<'a, str>,
 }
 
-impl BlockQuote {
-    pub fn new(value: &str) -> Self {
+impl<'a> BlockQuote<'a> {
+    pub fn new(value: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            value: value.to_string(),
+            value: value.into(),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Text {
-    pub value: String,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Text<'a> {
+    pub value: Cow<'a, str>,
 }
 
-impl Text {
-    pub fn new(value: &str) -> Self {
+impl<'a> Text<'a> {
+    pub fn new(value: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            value: value.to_string(),
+            value: value.into(),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct InternalLink {
-    pub title: String,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InternalLink<'a> {
+    pub title: Cow<'a, str>,
 }
 
-impl InternalLink {
-    pub fn new(title: &str) -> Self {
+impl<'a> InternalLink<'a> {
+    pub fn new(title: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            title: title.to_string(),
+            title: title.into(),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct ExternalLink {
-    pub title: Option<String>,
-    pub url: String,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExternalLink<'a> {
+    pub title: Option<Cow<'a, str>>,
+    pub url: Cow<'a, str>,
 }
 
-impl ExternalLink {
-    pub fn new(title: Option<&str>, url: &str) -> Self {
+impl<'a> ExternalLink<'a> {
+    pub fn new(title: Option<&'a str>, url: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            title: title.map(String::from),
-            url: url.to_string(),
+            title: title.map(Cow::Borrowed),
+            url: url.into(),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Heading {
-    pub text: String,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heading<'a> {
+    pub text: Cow<'a, str>,
     pub level: u8,
 }
 
-impl Heading {
-    pub fn new(text: &str, level: u8) -> Self {
+impl<'a> Heading<'a> {
+    pub fn new(text: impl Into<Cow<'a, str>>, level: u8) -> Self {
         Self {
-            text: text.to_string(),
+            text: text.into(),
             level,
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct Emphasis {
-    pub text: String,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Emphasis<'a> {
+    pub text: Cow<'a, str>,
     pub bold: u8,
     pub italic: u8,
     pub strikethrough: u8,
 }
 
-impl Emphasis {
-    pub fn new(text: &str, bold: u8, italic: u8, strikethrough: u8) -> Self {
+impl<'a> Emphasis<'a> {
+    pub fn new(text: impl Into<Cow<'a, str>>, bold: u8, italic: u8, strikethrough: u8) -> Self {
         Self {
-            text: text.to_string(),
+            text: text.into(),
             bold,
             italic,
             strikethrough,
         }
     }
 
-    pub fn bold(text: &str) -> Self {
+    pub fn bold(text: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            text: text.to_string(),
+            text: text.into(),
             bold: 1,
             ..Default::default()
         }
     }
 
-    pub fn bold_level(text: &str, level: u8) -> Self {
+    pub fn bold_level(text: impl Into<Cow<'a, str>>, level: u8) -> Self {
         Self {
-            text: text.to_string(),
+            text: text.into(),
             bold: level,
             ..Default::default()
         }
     }
 
-    pub fn italic(text: &str) -> Self {
+    pub fn italic(text: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            text: text.to_string(),
+            text: text.into(),
             italic: 1,
             ..Default::default()
         }
     }
 
-    pub fn strikethrough(text: &str) -> Self {
+    pub fn strikethrough(text: impl Into<Cow<'a, str>>) -> Self {
         Self {
-            text: text.to_string(),
+            text: text.into(),
             strikethrough: 1,
             ..Default::default()
         }
     }
 }
+
+fn owned(value: Cow<'_, str>) -> Cow<'static, str> {
+    Cow::Owned(value.into_owned())
+}
+
+impl Page<'_> {
+    /// Copies all borrowed text, so the page no longer refers to its input.
+    pub fn into_owned(self) -> Page<'static> {
+        Page {
+            lines: self.lines.into_iter().map(Line::into_owned).collect(),
+            meta: self.meta,
+        }
+    }
+}
+
+impl Line<'_> {
+    pub fn into_owned(self) -> Line<'static> {
+        Line {
+            kind: self.kind,
+            values: self.values.into_iter().map(Syntax::into_owned).collect(),
+        }
+    }
+}
+
+impl Syntax<'_> {
+    pub fn into_owned(self) -> Syntax<'static> {
+        Syntax {
+            kind: match self.kind {
+                SyntaxKind::HashTag(v) => SyntaxKind::HashTag(HashTag {
+                    value: owned(v.value),
+                }),
+                SyntaxKind::Bracket(v) => SyntaxKind::Bracket(v.into_owned()),
+                SyntaxKind::BlockQuote(v) => SyntaxKind::BlockQuote(BlockQuote {
+                    value: owned(v.value),
+                }),
+                SyntaxKind::Text(v) => SyntaxKind::Text(Text {
+                    value: owned(v.value),
+                }),
+            },
+        }
+    }
+}
+
+impl Bracket<'_> {
+    pub fn into_owned(self) -> Bracket<'static> {
+        Bracket {
+            kind: match self.kind {
+                BracketKind::InternalLink(v) => BracketKind::InternalLink(InternalLink {
+                    title: owned(v.title),
+                }),
+                BracketKind::ExternalLink(v) => BracketKind::ExternalLink(ExternalLink {
+                    title: v.title.map(owned),
+                    url: owned(v.url),
+                }),
+                BracketKind::Emphasis(v) => BracketKind::Emphasis(Emphasis {
+                    text: owned(v.text),
+                    ..v
+                }),
+                BracketKind::Heading(v) => BracketKind::Heading(Heading {
+                    text: owned(v.text),
+                    level: v.level,
+                }),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every string in `page`.
+    fn strings<'p>(page: &'p Page) -> Vec<&'p Cow<'p, str>> {
+        let mut strings = vec![];
+        for syntax in page.lines.iter().flat_map(|line| &line.values) {
+            match &syntax.kind {
+                SyntaxKind::HashTag(v) => strings.push(&v.value),
+                SyntaxKind::BlockQuote(v) => strings.push(&v.value),
+                SyntaxKind::Text(v) => strings.push(&v.value),
+                SyntaxKind::Bracket(v) => match &v.kind {
+                    BracketKind::InternalLink(v) => strings.push(&v.title),
+                    BracketKind::ExternalLink(v) => {
+                        strings.extend(&v.title);
+                        strings.push(&v.url);
+                    }
+                    BracketKind::Emphasis(v) => strings.push(&v.text),
+                    BracketKind::Heading(v) => strings.push(&v.text),
+                },
+            }
+        }
+        strings
+    }
+
+    #[test]
+    fn borrowed_test() {
+        let page =
+            crate::parse_page("`code` [Page] #tag\n\t[* bold] [title https://x.io] text").unwrap();
+        let strings = strings(&page);
+        assert_eq!(strings.len(), 10);
+        assert!(strings.iter().all(|s| matches!(s, Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn into_owned_test() {
+        let input = String::from("`code` [Page] #tag\n\t[* bold] [title https://x.io] text");
+        let page = crate::parse_page(&input).unwrap();
+        let expected = format!("{:?}", page);
+        let owned = page.into_owned();
+        drop(input);
+
+        assert_eq!(format!("{:?}", owned), expected);
+        assert!(strings(&owned).iter().all(|s| matches!(s, Cow::Owned(_))));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_representation() {
+        let line = Line::new(
+            LineKind::List(List::disc(1)),
+            vec![Syntax::new(SyntaxKind::Bracket(Bracket::new(
+                BracketKind::InternalLink(InternalLink::new("Page")),
+            )))],
+        );
+        let json = serde_json::to_string(&line).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":{"type":"list","kind":"disc","level":1},"values":[{"kind":{"type":"bracket","kind":{"type":"internal_link","title":"Page"}}}]}"#
+        );
+        assert_eq!(serde_json::from_str::<Line>(&json).unwrap(), line);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn list_level_test() {
+        let json = r#"{"kind":{"type":"list","kind":"disc","level":0},"values":[]}"#;
+        let error = serde_json::from_str::<Line>(json).unwrap_err();
+        assert!(error.to_string().contains("list level must be at least 1"));
+    }
+}
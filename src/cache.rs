@@ -0,0 +1,162 @@
+//! Manifest of the files written into an output directory, so that a
+//! conversion can skip pages whose output would come out the same.
+//!
+//! Each output file is recorded with a hash of the page it was generated
+//! from and a hash of everything else the output depends on: the converter
+//! version, the settings, the format and, when the link graph is in use, the
+//! backlinks.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    hash::Hasher,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Name of the manifest inside the output directory.
+pub const FILE_NAME: &str = ".scrapbox-converter-cache";
+
+/// Hashes of the inputs an output file was generated from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub input: u64,
+    pub config: u64,
+}
+
+/// Output files of the last conversion, by path relative to the output
+/// directory.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Manifest {
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the manifest of `dir`, or an empty one if there is none yet.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        match fs::read_to_string(dir.join(FILE_NAME)) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        fs::write(dir.join(FILE_NAME), self.to_string())
+    }
+
+    /// Reads one `<input> <config> <path>` line per file. Lines that do not
+    /// read are dropped, which only costs regenerating their files.
+    pub fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, ' ');
+                let input = u64::from_str_radix(fields.next()?, 16).ok()?;
+                let config = u64::from_str_radix(fields.next()?, 16).ok()?;
+                Some((PathBuf::from(fields.next()?), Entry { input, config }))
+            })
+            .collect();
+        Self { entries }
+    }
+
+    pub fn insert(&mut self, path: impl Into<PathBuf>, entry: Entry) {
+        self.entries.insert(path.into(), entry);
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Entry> {
+        self.entries.get(path).copied()
+    }
+
+    /// Whether `path` in `dir` was generated from the same inputs and is
+    /// still there.
+    pub fn is_fresh(&self, dir: &Path, path: &Path, entry: Entry) -> bool {
+        self.get(path) == Some(entry) && dir.join(path).is_file()
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (path, entry) in &self.entries {
+            writeln!(
+                f,
+                "{:016x} {:016x} {}",
+                entry.input,
+                entry.config,
+                path.display()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Hash of the `Debug` form of `value`.
+///
+/// Unlike `DefaultHasher`, the result does not change between builds of the
+/// same version. The `Debug` form of a type may change in a new version, so
+/// callers hash the version along with the settings.
+pub fn hash(value: &impl fmt::Debug) -> u64 {
+    let mut hasher = Fnv::default();
+    fmt::write(&mut hasher, format_args!("{:?}", value)).expect("hashing can not fail");
+    hasher.finish()
+}
+
+/// 64-bit FNV-1a.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+impl fmt::Write for Fnv {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        Hasher::write(self, s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_test() {
+        let mut manifest = Manifest::new();
+        let entry = Entry {
+            input: hash(&"page"),
+            config: 1,
+        };
+        manifest.insert("foo bar.md", entry);
+
+        let manifest = Manifest::parse(&manifest.to_string());
+        assert_eq!(manifest.get(Path::new("foo bar.md")), Some(entry));
+        assert_eq!(manifest.get(Path::new("foo")), None);
+        assert_eq!(Manifest::parse("not a manifest\n"), Manifest::new());
+    }
+
+    #[test]
+    fn hash_test() {
+        // The FNV-1a test vector for "a".
+        assert_eq!(hash(&format_args!("a")), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(&"page"), hash(&"page"));
+        assert_ne!(hash(&"page"), hash(&"Page"));
+    }
+}
@@ -0,0 +1,240 @@
+//! Project settings read from `scrapbox-converter.toml`:
+//!
+//! ```toml
+//! [markdown]
+//! indent = "  "
+//! link_style = "wiki"        # or "markdown"
+//! front_matter = "yaml"      # or "toml"; none when left out
+//! linked_from = true
+//! linked_from_heading = "Backlinks"
+//! two_hop = false
+//!
+//! [headings]
+//! h1_level = 3
+//! bold_to_h = true
+//! auto = false
+//!
+//! [projects]                 # where `[/project/page]` links point
+//! help = "https://scrapbox.io/help"
+//!
+//! [[pass]]                   # replaces [headings], as in a pipeline file
+//! name = "strip_tags"
+//! ```
+//!
+//! The file is looked up in the working directory and then in each of its
+//! parents. Every setting is optional.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "config")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "config")]
+use crate::pipeline::{PassEntry, PipelineConfig};
+use crate::{
+    pipeline::Pipeline,
+    visitor::markdown::{
+        FrontMatter, FrontMatterFormat, LinkStyle, LinkedFrom, MarkdownGenConfig, MarkdownPass,
+    },
+};
+
+pub const FILE_NAME: &str = "scrapbox-converter.toml";
+
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "config", serde(default, deny_unknown_fields))]
+#[non_exhaustive]
+pub struct Config {
+    pub markdown: MarkdownConfig,
+    pub headings: HeadingsConfig,
+    /// Base URL of each project that `[/project/page]` links point into.
+    pub projects: BTreeMap<String, String>,
+    /// Passes to run instead of `headings`.
+    #[cfg(feature = "config")]
+    #[serde(rename = "pass", skip_serializing_if = "Vec::is_empty")]
+    pub passes: Vec<PassEntry>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "config", serde(default, deny_unknown_fields))]
+#[non_exhaustive]
+pub struct MarkdownConfig {
+    pub indent: String,
+    pub link_style: LinkStyle,
+    pub front_matter: Option<FrontMatterFormat>,
+    /// Whether pages end with a list of the pages linking to them.
+    pub linked_from: bool,
+    pub linked_from_heading: String,
+    pub two_hop: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        let generator = MarkdownGenConfig::default();
+        let linked_from = LinkedFrom::default();
+        Self {
+            indent: generator.indent,
+            link_style: generator.link_style,
+            front_matter: None,
+            linked_from: true,
+            linked_from_heading: linked_from.heading,
+            two_hop: linked_from.two_hop,
+        }
+    }
+}
+
+/// Settings of the heading promotion run when no passes are configured.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "config", serde(default, deny_unknown_fields))]
+#[non_exhaustive]
+pub struct HeadingsConfig {
+    /// Bold level that becomes H1, between 1 and 6.
+    #[cfg_attr(
+        feature = "config",
+        serde(deserialize_with = "crate::pipeline::h1_level")
+    )]
+    pub h1_level: u8,
+    pub bold_to_h: bool,
+    pub auto: bool,
+}
+
+impl Default for HeadingsConfig {
+    fn default() -> Self {
+        Self {
+            h1_level: default_h1_level(),
+            bold_to_h: default_bold_to_h(),
+            auto: false,
+        }
+    }
+}
+
+/// Default `h1_level` of `[headings]` and of a `headings` pass.
+pub(crate) fn default_h1_level() -> u8 {
+    3
+}
+
+/// Default `bold_to_h` of `[headings]` and of a `headings` pass, so moving
+/// one into the other promotes the same lines.
+pub(crate) fn default_bold_to_h() -> bool {
+    true
+}
+
+impl Config {
+    /// The nearest `scrapbox-converter.toml` in `dir` or one of its parents.
+    #[cfg(feature = "config")]
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    #[cfg(feature = "config")]
+    pub fn from_toml(input: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(input)
+    }
+
+    #[cfg(feature = "config")]
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    pub fn markdown_gen(&self) -> MarkdownGenConfig {
+        let markdown = &self.markdown;
+        MarkdownGenConfig {
+            indent: markdown.indent.clone(),
+            front_matter: markdown.front_matter.map(FrontMatter::new),
+            linked_from: markdown.linked_from.then(|| LinkedFrom {
+                heading: markdown.linked_from_heading.clone(),
+                two_hop: markdown.two_hop,
+            }),
+            link_style: markdown.link_style,
+            projects: self.projects.clone(),
+        }
+    }
+
+    /// The configured passes, or else heading promotion when
+    /// `promote_headings` is set, as it is for output formats with headings.
+    pub fn pipeline(&self, promote_headings: bool) -> Result<Pipeline, regex::Error> {
+        #[cfg(feature = "config")]
+        if !self.passes.is_empty() {
+            return PipelineConfig {
+                passes: self.passes.clone(),
+            }
+            .build();
+        }
+        if !promote_headings {
+            return Ok(Pipeline::new());
+        }
+
+        let headings = &self.headings;
+        let mut pass = MarkdownPass::from_levels(headings.h1_level, headings.bold_to_h);
+        pass.auto = headings.auto;
+        Ok(Pipeline::new().with(pass))
+    }
+}
+
+#[cfg(all(test, feature = "config"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_toml_test() {
+        let config = Config::from_toml(
+            r#"
+            [markdown]
+            link_style = "wiki"
+            front_matter = "toml"
+
+            [projects]
+            help = "https://scrapbox.io/help"
+
+            [[pass]]
+            name = "strip_tags"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.markdown.link_style, LinkStyle::Wiki);
+        assert_eq!(config.markdown.indent, "   ");
+        assert_eq!(config.headings.h1_level, 3);
+        assert_eq!(config.pipeline(false).unwrap().names(), vec!["strip_tags"]);
+        assert_eq!(
+            Config::default().pipeline(true).unwrap().names(),
+            vec!["headings"]
+        );
+        assert!(Config::default()
+            .pipeline(false)
+            .unwrap()
+            .names()
+            .is_empty());
+
+        let printed = Config::from_toml(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(printed.projects, config.projects);
+        assert_eq!(printed.passes.len(), 1);
+
+        // `[headings]` and a `headings` pass promote the same lines.
+        let page = || crate::parse_page("[* small]\n[*** big]").unwrap();
+        let (mut from_headings, mut from_pass) = (page(), page());
+        Config::from_toml("[headings]")
+            .unwrap()
+            .pipeline(true)
+            .unwrap()
+            .run(&mut from_headings);
+        Config::from_toml("[[pass]]\nname = \"headings\"")
+            .unwrap()
+            .pipeline(true)
+            .unwrap()
+            .run(&mut from_pass);
+        assert_eq!(from_headings, from_pass);
+        assert!(matches!(
+            from_pass.lines[0].values[0].kind,
+            crate::SyntaxKind::Bracket(crate::Bracket {
+                kind: crate::BracketKind::Heading(_)
+            })
+        ));
+
+        assert!(Config::from_toml("[markdown]\nindnet = \"\"").is_err());
+        assert!(Config::from_toml("[headings]\nh1_level = 255").is_err());
+        assert!(Config::from_toml("[headings]\nh1_level = 0").is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    ast::{
+        Bracket, BracketKind, HashTag, InternalLink, Line, LineKind, List, Page, PageMeta, Syntax,
+        SyntaxKind,
+    },
+    title::normalize,
+    visitor::Visit,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LinkKind {
+    Link,
+    HashTag,
+}
+
+/// An outgoing reference from a page to another page of the project.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LinkRef {
+    pub target: String,
+    pub kind: LinkKind,
+    /// Zero-based line of the page text the reference is on, where every
+    /// line of a code block counts.
+    pub line: usize,
+}
+
+/// Internal links and hashtags between the pages of a project.
+///
+/// Titles are compared after [`normalize_title`](crate::normalize_title), so
+/// `[foo bar]` links to the page `Foo_Bar`. Links to other projects
+/// (`[/project/page]`) are not part of the graph.
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    /// Display title of every page and link target, by normalized title.
+    titles: BTreeMap<String, String>,
+    outgoing: BTreeMap<String, Vec<LinkRef>>,
+    incoming: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Pages related to one page, as Scrapbox lists them below it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Related {
+    /// Pages linking to the page.
+    pub backlinks: Vec<String>,
+    /// Pages sharing a link target with the page, but not linked directly.
+    pub two_hop: Vec<String>,
+}
+
+impl LinkGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, title: &str, page: &Page) {
+        let mut collector = LinkCollector::default();
+        collector.visit(page);
+
+        let key = normalize(title);
+        for link in &collector.links {
+            let target = normalize(&link.target);
+            self.titles
+                .entry(target.clone())
+                .or_insert_with(|| link.target.clone());
+            self.incoming.entry(target).or_default().insert(key.clone());
+        }
+        self.titles.insert(key.clone(), title.to_string());
+        self.outgoing.insert(key, collector.links);
+    }
+
+    fn title(&self, key: &str) -> &str {
+        &self.titles[key]
+    }
+
+    /// Titles of all pages added to the graph.
+    pub fn pages(&self) -> impl Iterator<Item = &str> {
+        self.outgoing.keys().map(|k| self.title(k))
+    }
+
+    pub fn contains(&self, title: &str) -> bool {
+        self.outgoing.contains_key(&normalize(title))
+    }
+
+    /// References from `title` in page order.
+    pub fn links(&self, title: &str) -> &[LinkRef] {
+        self.outgoing
+            .get(&normalize(title))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn outgoing_keys(&self, key: &str) -> BTreeSet<String> {
+        self.outgoing
+            .get(key)
+            .map(|links| links.iter().map(|l| normalize(&l.target)).collect())
+            .unwrap_or_default()
+    }
+
+    fn backlink_keys(&self, key: &str) -> BTreeSet<&str> {
+        self.incoming
+            .get(key)
+            .map(|pages| {
+                pages
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|p| *p != key)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Pages linking to `title`, whether or not `title` exists.
+    pub fn backlinks(&self, title: &str) -> BTreeSet<&str> {
+        self.backlink_keys(&normalize(title))
+            .into_iter()
+            .map(|k| self.title(k))
+            .collect()
+    }
+
+    /// Titles that are linked or tagged but have no page.
+    pub fn missing(&self) -> impl Iterator<Item = &str> {
+        self.incoming
+            .keys()
+            .filter(|k| !self.outgoing.contains_key(*k))
+            .map(|k| self.title(k))
+    }
+
+    /// A page for `title` listing every page that references it, like
+    /// Scrapbox shows for empty links and hashtags.
+    pub fn stub(&self, title: &str) -> Page<'_> {
+        let lines = self
+            .backlinks(title)
+            .into_iter()
+            .map(|source| {
+                Line::new(
+                    LineKind::List(List::disc(1)),
+                    vec![Syntax::new(SyntaxKind::Bracket(Bracket::new(
+                        BracketKind::InternalLink(InternalLink::new(source)),
+                    )))],
+                )
+            })
+            .collect();
+        Page {
+            lines,
+            meta: PageMeta {
+                title: Some(title.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn related(&self, title: &str) -> Related {
+        let key = normalize(title);
+        let backlinks = self.backlink_keys(&key);
+        let outgoing = self.outgoing_keys(&key);
+
+        let two_hop: BTreeSet<&str> = outgoing
+            .iter()
+            .flat_map(|target| self.backlink_keys(target))
+            .filter(|p| *p != key && !backlinks.contains(p) && !outgoing.contains(*p))
+            .collect();
+
+        let titles = |keys: BTreeSet<&str>| -> Vec<String> {
+            keys.into_iter()
+                .map(|k| self.title(k).to_string())
+                .collect()
+        };
+        Related {
+            backlinks: titles(backlinks),
+            two_hop: titles(two_hop),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LinkCollector {
+    line: usize,
+    links: Vec<LinkRef>,
+}
+
+impl LinkCollector {
+    fn push(&mut self, target: &str, kind: LinkKind) {
+        self.links.push(LinkRef {
+            target: target.to_string(),
+            kind,
+            line: self.line,
+        });
+    }
+}
+
+impl Visit for LinkCollector {
+    fn visit_page(&mut self, value: &Page) {
+        self.line = 0;
+        for line in value.lines.iter() {
+            self.visit_line(line);
+            self.line += line.source_lines();
+        }
+    }
+
+    fn visit_hashtag(&mut self, value: &HashTag) {
+        self.push(&value.value, LinkKind::HashTag);
+    }
+
+    fn visit_bracket_internal_link(&mut self, value: &InternalLink) {
+        if !value.title.starts_with('/') {
+            self.push(&value.title, LinkKind::Link);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::page;
+
+    #[test]
+    fn related_test() {
+        let mut graph = LinkGraph::new();
+        for (title, text) in [
+            ("a", "[b] #topic\n"),
+            ("b", "[a] [/other/a]\n"),
+            ("c", "see #Topic\n"),
+            ("D_page", "[c]\n"),
+        ] {
+            graph.add(title, &page(text).unwrap().1);
+        }
+
+        assert_eq!(graph.links("b").len(), 1);
+        assert_eq!(graph.backlinks("topic"), BTreeSet::from(["a", "c"]));
+        assert_eq!(graph.backlinks("C"), BTreeSet::from(["D_page"]));
+        assert!(graph.contains("d page"));
+        assert_eq!(
+            graph.related("a"),
+            Related {
+                backlinks: vec!["b".to_string()],
+                two_hop: vec!["c".to_string()],
+            }
+        );
+        assert_eq!(graph.links("c")[0].kind, LinkKind::HashTag);
+
+        assert_eq!(graph.missing().collect::<Vec<_>>(), vec!["topic"]);
+        let stub = graph.stub("topic");
+        assert_eq!(stub.meta.title.as_deref(), Some("topic"));
+        assert_eq!(stub.lines.len(), 2);
+    }
+}
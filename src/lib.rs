@@ -0,0 +1,64 @@
+//! Converts Scrapbox pages to Markdown, Logseq, Org mode and plain text, and
+//! Markdown back to Scrapbox.
+//!
+//! A page is parsed into the syntax tree of [`ast`], optionally transformed
+//! by [`Visitor`] passes such as [`MarkdownPass`](visitor::markdown::MarkdownPass),
+//! and handed to one of the generators in [`visitor`]:
+//!
+//! ```
+//! use scrapbox_converter::{
+//!     parse_page,
+//!     visitor::markdown::{MarkdownGen, MarkdownGenConfig, MarkdownPass},
+//!     Visitor,
+//! };
+//!
+//! let mut page = parse_page("[*** Title]\nsee [other page] #tag").unwrap();
+//! MarkdownPass::default().visit(&mut page);
+//! let markdown = MarkdownGen::new(MarkdownGenConfig::default()).generate(&page);
+//! assert_eq!(
+//!     markdown,
+//!     "# Title\nsee [other page](other_page.md) [#tag](tag.md)\n"
+//! );
+//! ```
+//!
+//! [`parse_markdown`] reads Markdown into the same tree, and
+//! [`ScrapboxGen`](visitor::scrapbox::ScrapboxGen) writes a tree back as
+//! Scrapbox text.
+//!
+//! [`project`] converts whole projects: it reads page files, runs the
+//! [`Pipeline`] of passes set up by a [`Config`] and writes one file per page,
+//! with the backlinks a [`LinkGraph`] finds. [`LinkReport`] lists broken links
+//! and [`Stats`] counts what pages contain.
+//!
+//! # Stability
+//!
+//! The crate follows semantic versioning. Items re-exported here, the public
+//! modules and the serialized form of the AST are the public API; the other
+//! modules are internal. Generator and settings structs are
+//! `#[non_exhaustive]` so options can be added in minor releases: start from
+//! `Default::default()` and set the fields you need.
+
+pub mod ast;
+mod cache;
+mod config;
+mod graph;
+pub mod parser;
+mod pipeline;
+mod pool;
+pub mod project;
+mod report;
+mod stats;
+mod title;
+pub mod visitor;
+
+pub use ast::*;
+pub use config::{Config, HeadingsConfig, MarkdownConfig, FILE_NAME as CONFIG_FILE_NAME};
+pub use graph::{LinkGraph, LinkKind, LinkRef, Related};
+pub use parser::{markdown::parse_markdown, parse_page, ParseError};
+#[cfg(feature = "config")]
+pub use pipeline::{MatchKind, PassConfig, PassEntry, PipelineConfig, RuleConfig};
+pub use pipeline::{Pass, Pipeline};
+pub use report::{BrokenLink, LinkReport, MissingPage};
+pub use stats::Stats;
+pub use title::normalize as normalize_title;
+pub use visitor::{Fold, Visit, Visitor};
@@ -1,48 +1,733 @@
-use std::{env, fs, io, path::Path};
+#[cfg(any(feature = "config", feature = "watch"))]
+use std::fs;
+#[cfg(feature = "watch")]
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::mpsc,
+    time::Instant,
+};
+use std::{
+    error::Error,
+    io::{self, Read},
+    num::NonZeroUsize,
+    path::PathBuf,
+    process::ExitCode,
+    thread,
+};
 
-pub use ast::*;
-use parser::page;
-use visitor::{
-    markdown::{MarkdownGen, MarkdownGenConfig, MarkdownPass},
-    Visitor,
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use scrapbox_converter::{
+    project::{self, ConvertOptions, OutputFormat, Source, Written},
+    visitor::markdown::{FrontMatterFormat, LinkStyle},
+    Config, LinkReport, Page, Stats,
 };
+#[cfg(feature = "config")]
+use scrapbox_converter::{PipelineConfig, CONFIG_FILE_NAME as FILE_NAME};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Convert Scrapbox pages to Markdown and other formats.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Settings file to use instead of the nearest scrapbox-converter.toml
+    #[cfg(feature = "config")]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert pages to another format
+    Convert(ConvertArgs),
+    /// Convert pages into `--out` and again whenever they change
+    #[cfg(feature = "watch")]
+    Watch(ConvertArgs),
+    /// Report links to missing pages and pages nothing links to; fails on broken links
+    Check {
+        #[command(flatten)]
+        input: InputArgs,
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Print the syntax tree of pages
+    Ast {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Count lines, links and hashtags of pages
+    Stats {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Inspect the settings read from scrapbox-converter.toml
+    #[cfg(feature = "config")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[cfg(feature = "config")]
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective settings and the file they were read from
+    Print,
+}
+
+#[derive(Args)]
+struct InputArgs {
+    /// Page files, directories, glob patterns, or `-` for stdin.
+    /// `.json` files are read as a serialized syntax tree, and Markdown only
+    /// with `--from markdown`.
+    #[arg(required = true)]
+    inputs: Vec<String>,
+
+    /// Read every input in this format instead of going by its extension
+    #[arg(long, value_enum)]
+    from: Option<InputFormat>,
+
+    /// Number of pages processed at once [default: number of CPUs]
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+}
+
+impl InputArgs {
+    fn jobs(&self) -> usize {
+        self.jobs
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get)
+    }
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+    format: Format,
+
+    /// Write one file per page into this directory instead of printing to stdout
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+
+    /// Also write a page for every hashtag and linked title that has none
+    #[arg(long, requires = "out")]
+    stubs: bool,
+
+    /// Print each page to stderr once it is converted
+    #[arg(long)]
+    progress: bool,
+
+    /// Write every page, even those unchanged since the last conversion into `--out`
+    #[arg(long, requires = "out")]
+    force: bool,
+
+    /// Pipeline of passes to run instead of heading promotion (TOML)
+    #[cfg(feature = "config")]
+    #[arg(long)]
+    pipeline: Option<PathBuf>,
+
+    /// Bold level that becomes H1 (`[*** x]` by default), from 1 to 6
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=6))]
+    h1_level: Option<u8>,
+
+    /// Promote single bold lines (`[* x]`) to headings [default: true]
+    #[arg(long, action = ArgAction::Set)]
+    bold_to_h: Option<bool>,
+
+    /// Derive the heading levels from the bold levels used on each page
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    auto_headings: Option<bool>,
+
+    /// Indentation of one list level in Markdown [default: 3 spaces]
+    #[arg(long)]
+    indent: Option<String>,
+
+    /// How links to other pages are written in Markdown
+    #[arg(long, value_enum)]
+    link_style: Option<LinkStyleArg>,
+
+    /// Write front matter for static site generators
+    #[arg(long, value_enum)]
+    front_matter: Option<FrontMatterArg>,
+
+    /// Write the section listing pages that link to a page, under this
+    /// heading [default: Linked from]
+    #[arg(long, overrides_with = "no_linked_from")]
+    linked_from: Option<String>,
+
+    /// Leave out the "Linked from" section
+    #[arg(long)]
+    no_linked_from: bool,
+
+    /// List pages sharing a link in the "Linked from" section too
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    two_hop: Option<bool>,
+}
+
+impl ConvertArgs {
+    fn options(&self) -> ConvertOptions {
+        let mut options = ConvertOptions::default();
+        options.format = self.format.into();
+        options.stubs = self.stubs;
+        options.force = self.force;
+        options.jobs = self.input.jobs();
+        options
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Markdown,
+    Logseq,
+    Org,
+    Text,
+    Scrapbox,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Markdown => OutputFormat::Markdown,
+            Format::Logseq => OutputFormat::Logseq,
+            Format::Org => OutputFormat::Org,
+            Format::Text => OutputFormat::Text,
+            Format::Scrapbox => OutputFormat::Scrapbox,
+            #[cfg(feature = "serde")]
+            Format::Json => OutputFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum InputFormat {
+    Scrapbox,
+    Markdown,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+impl From<InputFormat> for project::InputFormat {
+    fn from(format: InputFormat) -> Self {
+        match format {
+            InputFormat::Scrapbox => project::InputFormat::Scrapbox,
+            InputFormat::Markdown => project::InputFormat::Markdown,
+            #[cfg(feature = "serde")]
+            InputFormat::Json => project::InputFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FrontMatterArg {
+    Yaml,
+    Toml,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LinkStyleArg {
+    Markdown,
+    Wiki,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Text,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Convert(args) => load_config(&cli)
+            .and_then(|config| convert(args, config))
+            .map(|_| true),
+        #[cfg(feature = "watch")]
+        Command::Watch(args) => load_config(&cli)
+            .and_then(|config| watch(args, config))
+            .map(|_| true),
+        Command::Check { input, format } => check(input, *format),
+        Command::Ast { input } => ast(input).map(|_| true),
+        Command::Stats { input } => stats(input).map(|_| true),
+        #[cfg(feature = "config")]
+        Command::Config {
+            command: ConfigCommand::Print,
+        } => print_config(&cli).map(|_| true),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The file given with `--config`, or else the nearest one.
+#[cfg(feature = "config")]
+fn config_path(cli: &Cli) -> Result<Option<PathBuf>> {
+    match &cli.config {
+        Some(path) => Ok(Some(path.clone())),
+        None => Ok(Config::find(&std::env::current_dir()?)),
+    }
+}
+
+#[cfg_attr(not(feature = "config"), allow(unused_variables))]
+fn load_config(cli: &Cli) -> Result<Config> {
+    #[cfg(feature = "config")]
+    if let Some(path) = config_path(cli)? {
+        let contents =
+            fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(Config::from_toml(&contents).map_err(|e| format!("{}: {}", path.display(), e))?);
+    }
+    Ok(Config::default())
+}
+
+#[cfg(feature = "config")]
+fn print_config(cli: &Cli) -> Result<()> {
+    match config_path(cli)? {
+        Some(path) => println!("# {}", path.display()),
+        None => println!("# no {} found, using defaults", FILE_NAME),
+    }
+    print!("{}", load_config(cli)?.to_toml()?);
+    Ok(())
+}
+
+/// Applies the flags given on the command line over the settings file.
+fn override_config(args: &ConvertArgs, config: &mut Config) -> Result<()> {
+    let (headings, markdown) = (&mut config.headings, &mut config.markdown);
+    if let Some(h1_level) = args.h1_level {
+        headings.h1_level = h1_level;
+    }
+    if let Some(bold_to_h) = args.bold_to_h {
+        headings.bold_to_h = bold_to_h;
+    }
+    if let Some(auto) = args.auto_headings {
+        headings.auto = auto;
+    }
+    if let Some(indent) = &args.indent {
+        markdown.indent = indent.clone();
+    }
+    if let Some(link_style) = args.link_style {
+        markdown.link_style = match link_style {
+            LinkStyleArg::Markdown => LinkStyle::Markdown,
+            LinkStyleArg::Wiki => LinkStyle::Wiki,
+        };
+    }
+    if let Some(front_matter) = args.front_matter {
+        markdown.front_matter = Some(match front_matter {
+            FrontMatterArg::Yaml => FrontMatterFormat::Yaml,
+            FrontMatterArg::Toml => FrontMatterFormat::Toml,
+        });
+    }
+    if let Some(heading) = &args.linked_from {
+        markdown.linked_from = true;
+        markdown.linked_from_heading = heading.clone();
+    }
+    if args.no_linked_from {
+        markdown.linked_from = false;
+    }
+    if let Some(two_hop) = args.two_hop {
+        markdown.two_hop = two_hop;
+    }
 
-mod ast;
-mod parser;
-mod visitor;
+    #[cfg(feature = "config")]
+    if let Some(path) = &args.pipeline {
+        config.passes = PipelineConfig::from_toml(&fs::read_to_string(path)?)?.passes;
+    } else if args.h1_level.is_some() || args.bold_to_h.is_some() || args.auto_headings.is_some() {
+        // Heading flags ask for heading promotion rather than the configured passes.
+        config.passes.clear();
+    }
+    Ok(())
+}
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+/// Converts the pages and returns the titles of those written.
+fn convert(args: &ConvertArgs, mut config: Config) -> Result<Vec<String>> {
+    override_config(args, &mut config)?;
+    let options = args.options();
+    let sources = read_sources(&args.input)?;
+    let pages = load_pages(&sources, options.jobs)?;
+    write(args, &config, &options, pages)
+}
 
-    let file_path = &args[1];
-    let file_name = &args[2];
+/// Runs the passes over `pages` and writes them, returning the titles of
+/// those written.
+fn write(
+    args: &ConvertArgs,
+    config: &Config,
+    options: &ConvertOptions,
+    mut pages: Vec<Page>,
+) -> Result<Vec<String>> {
+    for (name, report) in project::run_passes(&mut pages, config, options)? {
+        eprint!("{name}:\n{report}");
+    }
 
-    let contents = read_file(Path::new(&file_path), file_name).expect("can not read file");
+    let total = pages.len();
+    let mut converted = 0;
+    let mut written = vec![];
+    let on_page = |page: Written| {
+        if !page.stub {
+            converted += 1;
+            if args.progress {
+                let unchanged = if page.changed { "" } else { " (unchanged)" };
+                eprintln!("[{converted}/{total}] {}{unchanged}", page.title);
+            }
+        }
+        if page.changed {
+            written.push(page.title.to_string());
+        }
+    };
+    match &args.out {
+        Some(out) => project::write_dir(&pages, config, options, out, on_page)?,
+        None => project::write_to(&pages, config, options, io::stdout().lock(), on_page)?,
+    }
+    Ok(written)
+}
+
+/// Converts like `convert`, then again each time a page changes.
+///
+/// Only the files that changed are parsed again. A page that no longer
+/// parses is logged and keeps its last good version, and only pages whose
+/// text or backlinks changed are rewritten, as the cache manifest in `--out`
+/// tells them apart.
+#[cfg(feature = "watch")]
+fn watch(args: &ConvertArgs, mut config: Config) -> Result<()> {
+    use std::{iter, path::PathBuf, time::Duration};
 
-    // let input = "[** Hello World]";
+    use notify::{RecursiveMode, Watcher};
+
+    // Editors save a file in several steps; events this close together are
+    // handled as one change.
+    const SETTLE: Duration = Duration::from_millis(100);
+
+    let Some(out) = &args.out else {
+        return Err("watch needs an output directory (--out)".into());
+    };
+    if args.input.inputs.iter().any(|i| i == "-") {
+        return Err("stdin can not be watched".into());
+    }
+    override_config(args, &mut config)?;
+    let mut options = args.options();
+    let from = args.input.from.map(project::InputFormat::from);
+    fs::create_dir_all(out)?;
+    let out = out.canonicalize()?;
 
-    let (_, mut p) = page(&contents).unwrap();
-    let mut pass = MarkdownPass {
-        h1_level: 3,
-        bold_to_h: true,
+    // The last good parse of every page file, by canonical path.
+    let mut pages: BTreeMap<PathBuf, Page<'static>> = BTreeMap::new();
+    let mut run = |change: &str, pages: &BTreeMap<PathBuf, Page<'static>>| {
+        let start = Instant::now();
+        let mut pages: Vec<Page> = pages.values().cloned().collect();
+        for (title, first) in project::remove_duplicates(&mut pages) {
+            eprintln!("duplicate page: {title} is the same as {first}");
+        }
+        match write(args, &config, &options, pages) {
+            Ok(written) => {
+                let noun = if written.len() == 1 { "page" } else { "pages" };
+                let elapsed = start.elapsed();
+                eprintln!("{change}: wrote {} {noun} in {elapsed:.1?}", written.len());
+            }
+            Err(e) => eprintln!("{change}: error: {e}"),
+        }
+        // Only the first run rewrites everything.
+        options.force = false;
     };
-    pass.visit(&mut p);
-    let mut visitor = MarkdownGen::new(MarkdownGenConfig::default());
+    update_pages(&mut pages, &args.input.inputs, from, &BTreeSet::new());
+    run("converted", &pages);
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mut targets = BTreeMap::new();
+    for input in &args.input.inputs {
+        let path = Path::new(input);
+        let target = if path.is_dir() {
+            (path, RecursiveMode::NonRecursive)
+        } else if path.exists() {
+            // Editors often replace a file rather than write to it, which a
+            // watch on the file itself would not survive.
+            (parent_dir(path), RecursiveMode::NonRecursive)
+        } else {
+            // A glob pattern: the deepest directory it names, and below.
+            (parent_dir(path), RecursiveMode::Recursive)
+        };
+        targets.insert(target.0.canonicalize()?, target.1);
+    }
+    for (path, mode) in &targets {
+        watcher.watch(path, *mode)?;
+    }
+    eprintln!("watching for changes, press Ctrl-C to stop");
+
+    while let Ok(event) = rx.recv() {
+        let events = iter::once(event).chain(iter::from_fn(|| rx.recv_timeout(SETTLE).ok()));
+        let changed = changed_pages(events, from, &out);
+        if changed.is_empty() {
+            continue;
+        }
 
-    let markdown = visitor.generate(&mut p);
-    println!("{markdown}");
+        update_pages(&mut pages, &args.input.inputs, from, &changed);
+        let names: Vec<_> = changed
+            .iter()
+            .filter_map(|p| p.file_name())
+            .map(|n| n.to_string_lossy())
+            .collect();
+        run(&names.join(", "), &pages);
+    }
+    Ok(())
 }
 
-fn read_file(dir: &Path, name: &str) -> io::Result<String> {
-    if dir.is_dir() {
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let file_name = entry.file_name();
-            if file_name == name {
-                let contents = fs::read_to_string(entry.path())?;
-                return Ok(contents);
+/// Parses the page files named by `inputs` that are new or in `changed`
+/// into `pages`, and forgets those that are gone. A file that can not be
+/// read or parsed is logged and keeps its last good parse, unless it no
+/// longer exists.
+#[cfg(feature = "watch")]
+fn update_pages(
+    pages: &mut BTreeMap<PathBuf, Page<'static>>,
+    inputs: &[String],
+    from: Option<project::InputFormat>,
+    changed: &BTreeSet<PathBuf>,
+) {
+    let gone = |e: &io::Error| e.kind() == io::ErrorKind::NotFound;
+    let mut paths = BTreeSet::new();
+    for input in inputs {
+        for path in project::expand(input, from).unwrap_or_default() {
+            match path.canonicalize() {
+                Ok(path) => {
+                    paths.insert(path);
+                }
+                // Removed or renamed since it was listed.
+                Err(e) if gone(&e) => {}
+                Err(e) => eprintln!("error: {}: {e}", path.display()),
             }
         }
     }
-    Ok("".to_string())
+    pages.retain(|path, _| paths.contains(path));
+    for path in paths {
+        if pages.contains_key(&path) && !changed.contains(&path) {
+            continue;
+        }
+        match Source::read(path.clone(), from).and_then(|s| Ok(s.parse()?.into_owned())) {
+            Ok(p) => {
+                pages.insert(path, p);
+            }
+            Err(e) if e.source().and_then(|e| e.downcast_ref()).is_some_and(gone) => {
+                pages.remove(&path);
+            }
+            Err(e) => eprintln!("error: {e}"),
+        }
+    }
+}
+
+/// Page files in `from` that `events` created, changed or removed, leaving
+/// out those under `out`, where the converted pages are written. Errors are
+/// logged.
+#[cfg(feature = "watch")]
+fn changed_pages(
+    events: impl IntoIterator<Item = notify::Result<notify::Event>>,
+    from: Option<project::InputFormat>,
+    out: &Path,
+) -> BTreeSet<PathBuf> {
+    let mut changed = BTreeSet::new();
+    for result in events {
+        match result {
+            Ok(e) if !matches!(e.kind, notify::EventKind::Access(_)) => changed.extend(
+                e.paths
+                    .into_iter()
+                    .filter(|p| project::is_page(p, from) && !p.starts_with(out)),
+            ),
+            Ok(_) => {}
+            Err(e) => eprintln!("error: {e}"),
+        }
+    }
+    changed
+}
+
+/// The deepest existing directory containing `path`.
+#[cfg(feature = "watch")]
+fn parent_dir(path: &Path) -> &Path {
+    path.ancestors()
+        .skip(1)
+        .find(|p| p.is_dir())
+        .unwrap_or(Path::new("."))
+}
+
+/// Prints broken links and orphan pages, returning whether no link is broken.
+fn check(input: &InputArgs, format: ReportFormat) -> Result<bool> {
+    let sources = read_sources(input)?;
+    let graph = project::link_graph(&load_pages(&sources, input.jobs())?);
+
+    let report = LinkReport::new(&graph);
+    match format {
+        ReportFormat::Text => print!("{report}"),
+        #[cfg(feature = "serde")]
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(report.is_ok())
+}
+
+fn ast(input: &InputArgs) -> Result<()> {
+    for p in load_pages(&read_sources(input)?, input.jobs())? {
+        #[cfg(feature = "serde")]
+        println!("{}", serde_json::to_string_pretty(&p)?);
+        #[cfg(not(feature = "serde"))]
+        println!("{:#?}", p);
+    }
+    Ok(())
+}
+
+fn stats(input: &InputArgs) -> Result<()> {
+    let mut total = Stats::default();
+    for p in load_pages(&read_sources(input)?, input.jobs())? {
+        let stats = Stats::new(&p);
+        println!("{}: {}", project::page_title(&p), stats);
+        total += stats;
+    }
+    println!("total: {}", total);
+    Ok(())
+}
+
+fn read_sources(input: &InputArgs) -> Result<Vec<Source>> {
+    let from = input.from.map(project::InputFormat::from);
+    let mut sources = vec![];
+    for input in &input.inputs {
+        if input == "-" {
+            let mut contents = String::new();
+            io::stdin().read_to_string(&mut contents)?;
+            sources.push(Source {
+                path: None,
+                format: from.unwrap_or(project::InputFormat::Scrapbox),
+                contents,
+            });
+        } else {
+            for path in project::expand(input, from)? {
+                sources.push(Source::read(path, from)?);
+            }
+        }
+    }
+    Ok(sources)
+}
+
+/// Parses every source, skipping pages whose title duplicates an earlier one.
+fn load_pages(sources: &[Source], jobs: usize) -> Result<Vec<Page<'_>>> {
+    let mut pages = project::load_pages(sources, jobs)?;
+    for (title, first) in project::remove_duplicates(&mut pages) {
+        eprintln!("duplicate page: {title} is the same as {first}");
+    }
+    Ok(pages)
+}
+
+#[cfg(all(test, feature = "watch"))]
+mod tests {
+    use notify::{
+        event::{AccessKind, CreateKind, ModifyKind, RemoveKind},
+        Event, EventKind,
+    };
+
+    use super::*;
+
+    fn event(kind: EventKind, path: &str) -> notify::Result<Event> {
+        Ok(Event::new(kind).add_path(PathBuf::from(path)))
+    }
+
+    #[test]
+    fn changed_pages_test() {
+        let events = vec![
+            event(EventKind::Modify(ModifyKind::Any), "/p/a.txt"),
+            event(EventKind::Create(CreateKind::File), "/p/b.txt"),
+            event(EventKind::Modify(ModifyKind::Any), "/p/a.txt"),
+            event(EventKind::Remove(RemoveKind::File), "/p/sub/c.txt"),
+            event(EventKind::Access(AccessKind::Any), "/p/d.txt"),
+            event(EventKind::Modify(ModifyKind::Any), "/p/notes.swp"),
+            Err(notify::Error::generic("lost")),
+        ];
+        assert_eq!(
+            changed_pages(events, None, Path::new("/out")),
+            BTreeSet::from(["/p/a.txt", "/p/b.txt", "/p/sub/c.txt"].map(PathBuf::from))
+        );
+    }
+
+    #[test]
+    fn update_pages_test() {
+        let dir =
+            std::env::temp_dir().join(format!("scrapbox-converter-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.join(name), "[x]").unwrap();
+        }
+        let (a, b, c) = (dir.join("a.txt"), dir.join("b.txt"), dir.join("c.txt"));
+        let inputs = [
+            dir.to_str().unwrap().to_string(),
+            c.to_str().unwrap().to_string(),
+        ];
+        let mut pages = BTreeMap::new();
+        let titles = |pages: &BTreeMap<PathBuf, Page>| -> Vec<_> {
+            pages
+                .values()
+                .map(|p| project::page_title(p).to_string())
+                .collect()
+        };
+
+        update_pages(&mut pages, &inputs, None, &BTreeSet::new());
+        assert_eq!(titles(&pages), ["a", "b", "c"]);
+
+        // Files removed between the event and the update are dropped, also
+        // when an input names them.
+        let changed = BTreeSet::from([
+            a.canonicalize().unwrap(),
+            b.canonicalize().unwrap(),
+            c.canonicalize().unwrap(),
+        ]);
+        fs::remove_file(&b).unwrap();
+        fs::remove_file(&c).unwrap();
+        update_pages(&mut pages, &inputs, None, &changed);
+        assert_eq!(titles(&pages), ["a"]);
+
+        // A page that no longer parses keeps its last good parse.
+        #[cfg(feature = "serde")]
+        {
+            let d = dir.join("d.json");
+            fs::write(&d, r#"{"lines": []}"#).unwrap();
+            update_pages(&mut pages, &inputs[..1], None, &BTreeSet::new());
+            fs::write(&d, "{").unwrap();
+            let changed = BTreeSet::from([d.canonicalize().unwrap()]);
+            update_pages(&mut pages, &inputs[..1], None, &changed);
+            assert_eq!(titles(&pages), ["a", "d"]);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn changed_pages_out_test() {
+        let events = vec![
+            event(EventKind::Modify(ModifyKind::Any), "/p/out/a.md"),
+            event(EventKind::Create(CreateKind::File), "/p/out/sub/b.txt"),
+            event(EventKind::Modify(ModifyKind::Any), "/p/output.txt"),
+        ];
+        assert_eq!(
+            changed_pages(events, None, Path::new("/p/out")),
+            BTreeSet::from([PathBuf::from("/p/output.txt")])
+        );
+
+        let events = vec![
+            event(EventKind::Modify(ModifyKind::Any), "/p/a.md"),
+            event(EventKind::Modify(ModifyKind::Any), "/p/b.txt"),
+        ];
+        assert_eq!(
+            changed_pages(
+                events,
+                Some(project::InputFormat::Markdown),
+                Path::new("/out")
+            ),
+            BTreeSet::from([PathBuf::from("/p/a.md")])
+        );
+    }
 }
@@ -0,0 +1,401 @@
+//! Reads Markdown (CommonMark) into the syntax tree of a Scrapbox page.
+//!
+//! Headings become bold lines, `[*** H1]` by default, lists become indented
+//! list lines and fenced code becomes code blocks. Links to URLs become
+//! external links and relative links internal ones, with `[#tag](tag.md)`
+//! read back as a hashtag, so Markdown written by `MarkdownGen` converts back
+//! to the page it came from. Inline markup inside links, emphasis and
+//! headings is kept as plain text, as Scrapbox brackets do not nest.
+
+use std::borrow::Cow;
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use crate::{ast::*, title};
+
+#[non_exhaustive]
+pub struct MarkdownReaderConfig {
+    /// Bold level for H1; deeper headings get one `*` less per level.
+    pub h1_level: u8,
+}
+
+impl Default for MarkdownReaderConfig {
+    fn default() -> Self {
+        Self { h1_level: 3 }
+    }
+}
+
+/// Parses a Markdown document. Any text is valid Markdown, so this can not
+/// fail; front matter is skipped.
+pub fn parse_markdown<'a>(input: &'a str, config: &MarkdownReaderConfig) -> Page<'a> {
+    let options = Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_WIKILINKS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS;
+
+    let mut reader = Reader::new(config);
+    for event in Parser::new_ext(input, options) {
+        reader.event(event);
+    }
+    reader.finish_line();
+    Page {
+        lines: reader.lines,
+        ..Default::default()
+    }
+}
+
+/// Markup whose content is collected as plain text into a single node.
+enum Inline<'a> {
+    Heading(u8),
+    Emphasis,
+    Link(LinkType, CowStr<'a>),
+    Image(CowStr<'a>),
+}
+
+struct Reader<'a, 'c> {
+    config: &'c MarkdownReaderConfig,
+    lines: Vec<Line<'a>>,
+    /// Values of the line being read.
+    values: Vec<Syntax<'a>>,
+    /// Whether each enclosing list is numbered.
+    lists: Vec<bool>,
+    quote: usize,
+    /// An empty line is due before the next line, between top-level blocks.
+    separate: bool,
+    inline: Vec<Inline<'a>>,
+    /// Text of the outermost `inline` markup.
+    capture: String,
+    bold: u8,
+    italic: u8,
+    strikethrough: u8,
+    /// Name and text of the code block being read.
+    code: Option<(String, String)>,
+    metadata: bool,
+}
+
+impl<'a, 'c> Reader<'a, 'c> {
+    fn new(config: &'c MarkdownReaderConfig) -> Self {
+        Self {
+            config,
+            lines: vec![],
+            values: vec![],
+            lists: vec![],
+            quote: 0,
+            separate: false,
+            inline: vec![],
+            capture: String::new(),
+            bold: 0,
+            italic: 0,
+            strikethrough: 0,
+            code: None,
+            metadata: false,
+        }
+    }
+
+    fn event(&mut self, event: Event<'a>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(_) if self.metadata => {}
+            Event::Text(text) => match &mut self.code {
+                Some((_, code)) => code.push_str(&text),
+                None => self.text(text),
+            },
+            Event::Code(code) if self.inline.is_empty() => {
+                self.push(SyntaxKind::BlockQuote(BlockQuote::new(cow(code))))
+            }
+            Event::Code(code) => self.capture.push_str(&code),
+            Event::Html(html) => {
+                for line in html.lines() {
+                    self.text(CowStr::from(line.to_string()));
+                    self.finish_line();
+                }
+            }
+            Event::InlineHtml(html) => self.text(html),
+            Event::SoftBreak | Event::HardBreak if self.inline.is_empty() => self.finish_line(),
+            Event::SoftBreak | Event::HardBreak => self.capture.push(' '),
+            Event::Rule => self.separate = true,
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'a>) {
+        match tag {
+            Tag::Heading { level, .. } => self.inline.push(Inline::Heading(level as u8)),
+            Tag::BlockQuote(_) => self.quote += 1,
+            Tag::CodeBlock(kind) => {
+                self.finish_line();
+                let name = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                let name = if name.is_empty() { "text".into() } else { name };
+                self.code = Some((name, String::new()));
+            }
+            Tag::List(start) => {
+                self.finish_line();
+                self.lists.push(start.is_some());
+            }
+            Tag::Item => self.finish_line(),
+            Tag::Emphasis => self.emphasis(|r| r.italic += 1),
+            Tag::Strong => self.emphasis(|r| r.bold += 1),
+            Tag::Strikethrough => self.emphasis(|r| r.strikethrough += 1),
+            Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            } => self.inline.push(Inline::Link(link_type, dest_url)),
+            Tag::Image { dest_url, .. } => self.inline.push(Inline::Image(dest_url)),
+            Tag::MetadataBlock(_) => self.metadata = true,
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(_) | TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.end_inline();
+                if matches!(tag, TagEnd::Heading(_)) {
+                    self.end_block();
+                }
+            }
+            TagEnd::Link | TagEnd::Image => self.end_inline(),
+            TagEnd::Paragraph | TagEnd::HtmlBlock => self.end_block(),
+            TagEnd::BlockQuote(_) => {
+                self.quote -= 1;
+                self.end_block();
+            }
+            TagEnd::CodeBlock => {
+                if let Some((name, mut code)) = self.code.take() {
+                    if code.ends_with('\n') {
+                        code.pop();
+                    }
+                    let kind = LineKind::CodeBlock(CodeBlock::new(name, self.lists.len()));
+                    let text = Syntax::new(SyntaxKind::Text(Text::new(code)));
+                    self.push_line(Line::new(kind, vec![text]));
+                }
+                self.end_block();
+            }
+            TagEnd::Item => self.finish_line(),
+            TagEnd::List(_) => {
+                self.finish_line();
+                self.lists.pop();
+                self.end_block();
+            }
+            TagEnd::MetadataBlock(_) => self.metadata = false,
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: CowStr<'a>) {
+        if self.inline.is_empty() {
+            // Escapes and entities split text into several events; they are
+            // joined so that words stay whole.
+            if let Some(Syntax {
+                kind: SyntaxKind::Text(last),
+                ..
+            }) = self.values.last_mut()
+            {
+                last.value.to_mut().push_str(&text);
+                return;
+            }
+            self.push(SyntaxKind::Text(Text::new(cow(text))));
+        } else {
+            self.capture.push_str(&text);
+        }
+    }
+
+    /// Counts an emphasis, which becomes a node of its own unless it is
+    /// inside other markup.
+    fn emphasis(&mut self, count: impl FnOnce(&mut Self)) {
+        count(self);
+        self.inline.push(Inline::Emphasis);
+    }
+
+    /// Closes inline markup, turning the outermost into a node.
+    fn end_inline(&mut self) {
+        let Some(inline) = self.inline.pop() else {
+            return;
+        };
+        if !self.inline.is_empty() {
+            return;
+        }
+
+        let text = std::mem::take(&mut self.capture);
+        let (bold, italic, strikethrough) = (self.bold, self.italic, self.strikethrough);
+        (self.bold, self.italic, self.strikethrough) = (0, 0, 0);
+        let kind = match inline {
+            Inline::Heading(level) => {
+                let bold = self
+                    .config
+                    .h1_level
+                    .saturating_add(1)
+                    .saturating_sub(level)
+                    .max(1);
+                bracket(BracketKind::Emphasis(Emphasis::bold_level(text, bold)))
+            }
+            Inline::Emphasis => bracket(BracketKind::Emphasis(Emphasis::new(
+                text,
+                bold,
+                italic,
+                strikethrough,
+            ))),
+            Inline::Link(LinkType::Email, _) => SyntaxKind::Text(Text::new(text)),
+            Inline::Link(LinkType::WikiLink { .. }, dest) => {
+                bracket(BracketKind::InternalLink(InternalLink::new(cow(dest))))
+            }
+            Inline::Link(_, dest) if is_url(&dest) => {
+                let title = (!text.is_empty() && text != *dest).then(|| text.into());
+                bracket(BracketKind::ExternalLink(ExternalLink {
+                    title,
+                    url: cow(dest),
+                }))
+            }
+            Inline::Link(_, dest) if dest.starts_with('#') => SyntaxKind::Text(Text::new(text)),
+            // The page is the one the URL names; the link text is kept as its
+            // title only when it names the same page, as `MarkdownGen` writes.
+            Inline::Link(_, dest) => {
+                let title = title::from_link_path(&dest);
+                match text.strip_prefix('#') {
+                    Some(tag) if title::normalize(tag) == title::normalize(&title) => {
+                        SyntaxKind::HashTag(HashTag::new(tag.to_string()))
+                    }
+                    _ if title::normalize(&text) == title::normalize(&title) => {
+                        bracket(BracketKind::InternalLink(InternalLink::new(text)))
+                    }
+                    _ => bracket(BracketKind::InternalLink(InternalLink::new(title))),
+                }
+            }
+            // Scrapbox shows a linked image URL as the image.
+            Inline::Image(dest) if is_url(&dest) => bracket(BracketKind::ExternalLink(
+                ExternalLink::new(None, cow(dest)),
+            )),
+            Inline::Image(_) => SyntaxKind::Text(Text::new(text)),
+        };
+        self.push(kind);
+    }
+
+    fn push(&mut self, kind: SyntaxKind<'a>) {
+        if self.values.is_empty() && self.quote > 0 {
+            self.values
+                .push(Syntax::new(SyntaxKind::Text(Text::new("> "))));
+        }
+        self.values.push(Syntax::new(kind));
+    }
+
+    /// Ends the line being read, if anything was read into it.
+    fn finish_line(&mut self) {
+        if self.values.is_empty() {
+            return;
+        }
+        let kind = match self.lists.last() {
+            Some(&numbered) => {
+                let kind = if numbered {
+                    ListKind::Decimal
+                } else {
+                    ListKind::Disc
+                };
+                LineKind::List(List::new(kind, self.lists.len()))
+            }
+            None => LineKind::Normal,
+        };
+        let values = std::mem::take(&mut self.values);
+        self.push_line(Line::new(kind, values));
+    }
+
+    fn push_line(&mut self, line: Line<'a>) {
+        if self.separate && !self.lines.is_empty() {
+            self.lines.push(Line::new(LineKind::Normal, vec![]));
+        }
+        self.separate = false;
+        self.lines.push(line);
+    }
+
+    /// Ends a block; top-level blocks are kept apart by an empty line.
+    fn end_block(&mut self) {
+        self.finish_line();
+        if self.lists.is_empty() && self.quote == 0 {
+            self.separate = true;
+        }
+    }
+}
+
+fn bracket(kind: BracketKind<'_>) -> SyntaxKind<'_> {
+    SyntaxKind::Bracket(Bracket::new(kind))
+}
+
+fn is_url(dest: &str) -> bool {
+    dest.contains("://") || dest.starts_with("mailto:")
+}
+
+fn cow(s: CowStr<'_>) -> Cow<'_, str> {
+    match s {
+        CowStr::Borrowed(s) => Cow::Borrowed(s),
+        s => Cow::Owned(s.into_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::visitor::scrapbox::{ScrapboxGen, ScrapboxGenConfig};
+
+    fn scrapbox(markdown: &str) -> String {
+        let page = parse_markdown(markdown, &MarkdownReaderConfig::default());
+        ScrapboxGen::new(ScrapboxGenConfig::default()).generate(&page)
+    }
+
+    #[test]
+    fn blocks_test() {
+        assert_eq!(
+            scrapbox("# Title\n\nfirst line\nsecond line\n\n## Section\n\n> quoted"),
+            "[*** Title]\n\nfirst line\nsecond line\n\n[** Section]\n\n> quoted\n"
+        );
+        assert_eq!(
+            scrapbox("* a\n  * b\n* c\n\n1. one\n1. two"),
+            "\ta\n\t\tb\n\tc\n\n\t1. one\n\t2. two\n"
+        );
+        assert_eq!(
+            scrapbox("```rust\nfn main() {\n    f();\n}\n```\n\n    indented"),
+            "code:rust\n fn main() {\n     f();\n }\n\ncode:text\n indented\n"
+        );
+        assert_eq!(
+            scrapbox("* item\n\n  ```sh\n  ls\n  ```"),
+            "\titem\n\tcode:sh\n\t ls\n"
+        );
+        assert_eq!(scrapbox("---\ntitle: Page\n---\ntext"), "text\n");
+        let config = MarkdownReaderConfig { h1_level: u8::MAX };
+        assert_eq!(parse_markdown("###### deep", &config).lines.len(), 1);
+    }
+
+    #[test]
+    fn inline_test() {
+        assert_eq!(
+            scrapbox("**bold** *it* ~~gone~~ ***both*** `code`"),
+            "[* bold] [/ it] [- gone] [*/ both] `code`\n"
+        );
+        assert_eq!(
+            scrapbox(
+                "[Rust](https://www.rust-lang.org/) <https://scrapbox.io> ![](https://a.b/c.png)"
+            ),
+            "[Rust https://www.rust-lang.org/] [https://scrapbox.io] [https://a.b/c.png]\n"
+        );
+        assert_eq!(
+            scrapbox("[Other page](other_page.md) [#tag](tag.md) [[Wiki page]] [top](#top)"),
+            "[Other page] #tag [Wiki page] top\n"
+        );
+        assert_eq!(
+            scrapbox("read [the setup guide](docs/setup.md#install) and [](my%20page.md)"),
+            "read [setup] and [my page]\n"
+        );
+        assert_eq!(
+            scrapbox("[100%](100%2525.md) [#a_b](a_b.md) [#tag](other.md)"),
+            "[100%] #a_b [other]\n"
+        );
+        assert_eq!(scrapbox("# A [link](x.md)"), "[*** A link]\n");
+    }
+}
@@ -1,30 +1,67 @@
+use std::fmt;
+
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until, take_while},
+    bytes::complete::{tag, take_till, take_until, take_while},
     character::complete::{char, digit1, space0},
-    combinator::{map, opt, peek},
-    error::{ParseError, VerboseError},
+    combinator::{all_consuming, map, opt, recognize},
+    error::{ParseError as _, VerboseError},
     multi::{many0, many1},
-    sequence::{delimited, preceded, terminated},
+    sequence::{delimited, pair, preceded, terminated},
     Err, IResult,
 };
 
 use crate::ast::*;
 
-pub type Result<I, O, E = VerboseError<I>> = IResult<I, O, E>;
+pub mod markdown;
+
+pub(crate) type Result<I, O, E = VerboseError<I>> = IResult<I, O, E>;
+
+/// Error returned when a page can not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    message: String,
+}
 
-pub fn page(input: &str) -> Result<&str, Page> {
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses the text of a Scrapbox page.
+///
+/// The returned page has no metadata; its title and timestamps are up to the
+/// caller, which usually knows them from the file or the export.
+pub fn parse_page(input: &str) -> std::result::Result<Page<'_>, ParseError> {
+    page(input).map(|(_, page)| page).map_err(|e| ParseError {
+        message: e.to_string(),
+    })
+}
+
+pub(crate) fn page(input: &str) -> Result<&str, Page<'_>> {
     let (input, lines) = many0(line)(input)?;
 
-    Ok((input, Page { lines }))
+    Ok((
+        input,
+        Page {
+            lines,
+            ..Default::default()
+        },
+    ))
 }
 
-pub fn line(input: &str) -> Result<&str, Line> {
+pub(crate) fn line(input: &str) -> Result<&str, Line<'_>> {
     if input.is_empty() {
         return Err(Err::Error(VerboseError::from_char(input, ' ')));
     }
 
     let (input, _) = opt(char('\n'))(input)?;
+    if let Ok(result) = code_block(input) {
+        return Ok(result);
+    }
     let (input, list) = list(input)?;
     if let Some(list) = &list {
         map(many0(syntax), |c| {
@@ -40,7 +77,7 @@ pub fn line(input: &str) -> Result<&str, Line> {
     }
 }
 
-fn syntax(input: &str) -> Result<&str, Option<Syntax>> {
+fn syntax(input: &str) -> Result<&str, Option<Syntax<'_>>> {
     map(
         alt((
             map(hashtag, |s| Syntax::new(SyntaxKind::HashTag(s))),
@@ -58,135 +95,96 @@ fn syntax(input: &str) -> Result<&str, Option<Syntax>> {
 }
 
 // #tag
-fn hashtag(input: &str) -> Result<&str, HashTag> {
-    let terminators = vec![" ", "  ", "\n"];
-
+fn hashtag(input: &str) -> Result<&str, HashTag<'_>> {
     map(
-        preceded(
-            tag("#"),
-            take_while(move |c: char| !terminators.contains(&c.to_string().as_str())),
-        ),
-        |s: &str| HashTag {
-            value: s.to_string(),
-        },
+        preceded(tag("#"), take_till(|c| c == ' ' || c == '\n')),
+        |s: &str| HashTag { value: s.into() },
     )(input)
 }
 
-fn text(input: &str) -> Result<&str, Text> {
-    if input.is_empty() {
-        return Err(Err::Error(VerboseError::from_char(input, 'x')));
-    }
-
+/// Plain text up to the end of the line, the next bracket, backtick or a
+/// hashtag following a space, found in a single pass over the current line.
+/// A `[` or `` ` `` that `bracketing` or `block_quote` could not read, as it
+/// is not closed on its line, is text too.
+fn text(input: &str) -> Result<&str, Text<'_>> {
     if input.starts_with('#') {
         return Err(Err::Error(VerboseError::from_char(input, ' ')));
     }
 
-    fn take_until_tag(input: &str) -> Result<&str, &str> {
-        let (input, _) = peek(take_until(" #"))(input)?;
-        take_until("#")(input)
-    }
-
-    fn take_until_newline(input: &str) -> Result<&str, &str> {
-        let (input, text) = take_until("\n")(input)?;
-        Ok((input, text))
-    }
-
-    fn take_until_bracket(input: &str) -> Result<&str, &str> {
-        take_while(|c| c != '[')(input)
-    }
-
-    let ret = vec![
-        peek(take_until_tag)(input),
-        peek(take_until_newline)(input),
-        peek(take_until_bracket)(input),
-    ];
-
-    let ret = ret
-        .iter()
-        .filter(|r| r.is_ok())
-        .filter_map(|x| x.as_ref().ok())
-        .min_by(|(_, a), (_, b)| a.len().cmp(&b.len()));
-
-    match ret {
-        Some(&(input, consumed)) => {
-            if consumed.is_empty() {
-                return Err(Err::Error(VerboseError::from_char(input, ' ')));
-            }
-            let input = input.split_at(consumed.len()).1;
-            let text = Text {
-                value: consumed.to_string(),
-            };
-            Ok((input, text))
-        }
-        None => Err(Err::Error(VerboseError::from_char(input, ' '))),
+    let bytes = input.as_bytes();
+    let start = usize::from(input.starts_with(['[', '`']));
+    let end = (start..bytes.len())
+        .find(|&i| match bytes[i] {
+            b'\n' | b'[' | b'`' => true,
+            b'#' => i > 0 && bytes[i - 1] == b' ',
+            _ => false,
+        })
+        .unwrap_or(bytes.len());
+
+    if end == 0 {
+        return Err(Err::Error(VerboseError::from_char(input, ' ')));
     }
+    let (consumed, input) = input.split_at(end);
+    Ok((input, Text::new(consumed)))
 }
 
 // []
-fn bracketing(input: &str) -> Result<&str, Bracket> {
-    let (input, _) = peek(delimited(char('['), take_while(|c| c != ']'), char(']')))(input)?;
-    map(
-        alt((
-            map(emphasis, BracketKind::Emphasis),
-            map(external_link, BracketKind::ExternalLink),
-            map(internal_link, BracketKind::InternalLink),
-        )),
-        Bracket::new,
-    )(input)
+fn bracketing(input: &str) -> Result<&str, Bracket<'_>> {
+    // A bracket ends at the first `]` on its line; its contents are parsed
+    // on their own so that no parser can read past it.
+    let (input, bracket) = recognize(delimited(
+        char('['),
+        take_while(|c| c != ']' && c != '\n'),
+        char(']'),
+    ))(input)?;
+    let (_, kind) = all_consuming(alt((
+        map(emphasis, BracketKind::Emphasis),
+        map(external_link, BracketKind::ExternalLink),
+        map(internal_link, BracketKind::InternalLink),
+    )))(bracket)?;
+    Ok((input, Bracket::new(kind)))
 }
 
 // [internal_link]
-fn internal_link(input: &str) -> Result<&str, InternalLink> {
+fn internal_link(input: &str) -> Result<&str, InternalLink<'_>> {
     let (input, text) = delimited(char('['), take_while(|c| c != ']'), char(']'))(input)?;
     Ok((input, InternalLink::new(text)))
 }
 
-fn external_link_plain(input: &str) -> Result<&str, ExternalLink> {
-    let (input, protocol) = alt((tag("https://"), tag("http://")))(input)?;
-    let (input, url) = take_until(" ")(input)?;
-    Ok((
-        input,
-        ExternalLink::new(None, &format!("{}{}", protocol, url)),
-    ))
+fn external_link_plain(input: &str) -> Result<&str, ExternalLink<'_>> {
+    let (input, url) = recognize(pair(protocol, take_till(|c| c == ' ' || c == '\n')))(input)?;
+    Ok((input, ExternalLink::new(None, url)))
+}
+
+fn protocol(input: &str) -> Result<&str, &str> {
+    alt((tag("https://"), tag("http://")))(input)
 }
 
 // https://www.rust-lang.org/
 // [https://www.rust-lang.org/]
 // [https://www.rust-lang.org/ Rust]
 // [Rust https://www.rust-lang.org/]
-fn external_link(input: &str) -> Result<&str, ExternalLink> {
-    fn url(input: &str) -> Result<&str, ExternalLink> {
+fn external_link(input: &str) -> Result<&str, ExternalLink<'_>> {
+    fn url(input: &str) -> Result<&str, ExternalLink<'_>> {
         let (input, _) = opt(space0)(input)?;
-        let (input, protocol) = alt((tag("https://"), tag("http://")))(input)?;
-        let (input, url) = take_until("]")(input)?;
-        Ok((
-            input,
-            ExternalLink::new(None, &format!("{}{}", protocol, url)),
-        ))
+        let (input, url) = recognize(pair(protocol, take_until("]")))(input)?;
+        Ok((input, ExternalLink::new(None, url)))
     }
 
-    fn url_title(input: &str) -> Result<&str, ExternalLink> {
-        let (input, protocol) = alt((tag("https://"), tag("http://")))(input)?;
-        let (input, url) = take_until("]")(input)?;
+    fn url_title(input: &str) -> Result<&str, ExternalLink<'_>> {
+        let (input, url) = recognize(pair(protocol, take_until("]")))(input)?;
         let (input, _) = char(' ')(input)?;
         let (input, title) = take_until("]")(input)?;
 
-        Ok((
-            input,
-            ExternalLink::new(Some(title), &format!("{}{}", protocol, url)),
-        ))
+        Ok((input, ExternalLink::new(Some(title), url)))
     }
 
-    fn title_url(input: &str) -> Result<&str, ExternalLink> {
+    fn title_url(input: &str) -> Result<&str, ExternalLink<'_>> {
         let (input, title) = take_until(" ")(input)?;
         let (input, _) = char(' ')(input)?;
-        let (input, protocol) = alt((tag("https://"), tag("http://")))(input)?;
-        let (input, url) = take_until("]")(input)?;
+        let (input, url) = recognize(pair(protocol, take_until("]")))(input)?;
 
-        Ok((
-            input,
-            ExternalLink::new(Some(title), &format!("{}{}", protocol, url)),
-        ))
+        Ok((input, ExternalLink::new(Some(title), url)))
     }
 
     delimited(char('['), alt((url_title, title_url, url)), char(']'))(input)
@@ -200,7 +198,7 @@ fn external_link(input: &str) -> Result<&str, ExternalLink> {
 // [[Bold]] or [* Bold] or [*** Bold]
 // [/ italic]
 // [- strikethrough]
-fn emphasis(input: &str) -> Result<&str, Emphasis> {
+fn emphasis(input: &str) -> Result<&str, Emphasis<'_>> {
     let (input, text) = delimited(char('['), take_while(|c| c != ']'), char(']'))(input)?;
     let (rest, tokens) = take_while(|c| ['*', '/', '-'].contains(&c))(text)?;
     let (text, _) = char(' ')(rest)?;
@@ -229,14 +227,40 @@ fn emphasis(input: &str) -> Result<&str, Emphasis> {
 // fn math() {}
 
 // `block_quote`
-fn block_quote(input: &str) -> Result<&str, BlockQuote> {
+fn block_quote(input: &str) -> Result<&str, BlockQuote<'_>> {
     map(
-        delimited(char('`'), take_while(|c| c != '`'), char('`')),
+        delimited(char('`'), take_while(|c| c != '`' && c != '\n'), char('`')),
         BlockQuote::new,
     )(input)
 }
 
-// fn code_block() {}
+// code:main.rs
+//  fn main() {}
+fn code_block(input: &str) -> Result<&str, Line<'_>> {
+    let (input, tabs) = take_while(|c| c == '\t')(input)?;
+    let (mut input, name) = preceded(tag("code:"), take_while(|c| c != '\n'))(input)?;
+    let level = tabs.len();
+
+    // The block goes on as long as lines are indented deeper than `code:`.
+    let mut code = vec![];
+    while let Some(line) = input.strip_prefix('\n') {
+        let indent = line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+        if indent <= level {
+            break;
+        }
+        let end = line.find('\n').unwrap_or(line.len());
+        code.push(&line[level + 1..end]);
+        input = &line[end..];
+    }
+
+    Ok((
+        input,
+        Line::new(
+            LineKind::CodeBlock(CodeBlock::new(name, level)),
+            vec![Syntax::new(SyntaxKind::Text(Text::new(code.join("\n"))))],
+        ),
+    ))
+}
 
 // fn table() {}
 
@@ -293,6 +317,44 @@ mod tests {
         // assert!(hashtag("# tag").is_err());
     }
 
+    #[test]
+    fn text_test() {
+        assert!(text("").is_err());
+        assert!(text("#tag").is_err());
+        assert_eq!(text("[0 [link]"), Ok(("[link]", Text::new("[0 "))));
+        assert_eq!(text("abc"), Ok(("", Text::new("abc"))));
+        assert_eq!(text("abc\ndef"), Ok(("\ndef", Text::new("abc"))));
+        assert_eq!(text("abc [link]"), Ok(("[link]", Text::new("abc "))));
+        assert_eq!(text("abc #tag"), Ok(("#tag", Text::new("abc "))));
+        assert_eq!(text("C# #tag"), Ok(("#tag", Text::new("C# "))));
+        assert_eq!(text("abc\n #tag"), Ok(("\n #tag", Text::new("abc"))));
+        assert_eq!(text("a `b` c"), Ok(("`b` c", Text::new("a "))));
+    }
+
+    #[test]
+    fn bracketing_test() {
+        assert!(bracketing("[a\nb]").is_err());
+        assert_eq!(
+            bracketing("[abc] https://x.io]"),
+            Ok((
+                " https://x.io]",
+                Bracket::new(BracketKind::InternalLink(InternalLink::new("abc")))
+            ))
+        );
+    }
+
+    #[test]
+    fn external_link_plain_test() {
+        assert_eq!(
+            external_link_plain("https://x.io\nnext line"),
+            Ok(("\nnext line", ExternalLink::new(None, "https://x.io")))
+        );
+        assert_eq!(
+            external_link_plain("https://x.io"),
+            Ok(("", ExternalLink::new(None, "https://x.io")))
+        );
+    }
+
     #[test]
     fn emphasis_test() {
         assert_eq!(
@@ -314,6 +376,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn code_block_test() {
+        let code = |name, level, code: &str| {
+            Line::new(
+                LineKind::CodeBlock(CodeBlock::new(name, level)),
+                vec![Syntax::new(SyntaxKind::Text(Text::new(code.to_string())))],
+            )
+        };
+        assert!(code_block("code").is_err());
+        assert_eq!(
+            code_block("code:main.rs\n fn main() {\n \tf();\n }\nafter"),
+            Ok(("\nafter", code("main.rs", 0, "fn main() {\n\tf();\n}")))
+        );
+        assert_eq!(
+            code_block("\tcode:sh\n\t\tls\n\titem"),
+            Ok(("\n\titem", code("sh", 1, "ls")))
+        );
+        assert_eq!(code_block("code:sh\n"), Ok(("\n", code("sh", 0, ""))));
+    }
+
     #[test]
     fn test_block_quote() {
         assert!(block_quote("123abc").is_err());
@@ -323,5 +405,35 @@ mod tests {
             block_quote("`code` test"),
             Ok((" test", BlockQuote::new("code")))
         );
+        assert!(block_quote("`open\nnext`").is_err());
+    }
+
+    #[test]
+    fn unclosed_test() {
+        let texts = |input| {
+            parse_page(input)
+                .unwrap()
+                .lines
+                .iter()
+                .map(|l| l.values.clone())
+                .collect::<Vec<_>>()
+        };
+        let text = |value| Syntax::new(SyntaxKind::Text(Text::new(value)));
+        assert_eq!(
+            texts("array[0 is first\n[b]"),
+            vec![
+                vec![text("array"), text("[0 is first")],
+                vec![Syntax::new(SyntaxKind::Bracket(Bracket::new(
+                    BracketKind::InternalLink(InternalLink::new("b"))
+                )))],
+            ]
+        );
+        assert_eq!(
+            texts("a `open\n`code`"),
+            vec![
+                vec![text("a "), text("`open")],
+                vec![Syntax::new(SyntaxKind::BlockQuote(BlockQuote::new("code")))],
+            ]
+        );
     }
 }
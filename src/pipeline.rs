@@ -0,0 +1,411 @@
+use std::any::Any;
+
+use crate::{
+    ast::Page,
+    visitor::{
+        link_rewrite::LinkRewritePass, markdown::MarkdownPass, strip_tags::StripTagsPass, Visitor,
+    },
+};
+
+/// A named transformation that can be run as a step of a `Pipeline`.
+///
+/// Pages may be split between several pipelines built alike, each on its own
+/// thread; `merge` then adds the findings of the others to one of them.
+pub trait Pass: Any + Send {
+    fn name(&self) -> &str;
+
+    fn run(&mut self, page: &mut Page);
+
+    /// Human readable findings collected while running, if any.
+    fn report(&self) -> Option<String> {
+        None
+    }
+
+    /// Adds the findings of `other`, the same pass run over other pages.
+    fn merge(&mut self, _other: &dyn Pass) {}
+}
+
+impl Pass for MarkdownPass {
+    fn name(&self) -> &str {
+        "headings"
+    }
+
+    fn run(&mut self, page: &mut Page) {
+        self.visit(page);
+    }
+}
+
+impl Pass for StripTagsPass {
+    fn name(&self) -> &str {
+        "strip_tags"
+    }
+
+    fn run(&mut self, page: &mut Page) {
+        self.visit(page);
+    }
+}
+
+impl Pass for LinkRewritePass {
+    fn name(&self) -> &str {
+        "rewrite_links"
+    }
+
+    fn run(&mut self, page: &mut Page) {
+        self.visit(page);
+    }
+
+    fn report(&self) -> Option<String> {
+        Some(self.summary())
+    }
+
+    fn merge(&mut self, other: &dyn Pass) {
+        if let Some(other) = (other as &dyn Any).downcast_ref::<Self>() {
+            self.report.merge(&other.report);
+        }
+    }
+}
+
+/// Ordered list of passes run over a page before generation.
+#[derive(Default)]
+pub struct Pipeline {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, pass: impl Pass + 'static) -> Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.passes.iter().map(|p| p.name()).collect()
+    }
+
+    pub fn run(&mut self, page: &mut Page) {
+        for pass in self.passes.iter_mut() {
+            pass.run(page);
+        }
+    }
+
+    /// Adds the findings of `other`, built alike and run over other pages.
+    pub fn merge(&mut self, other: &Pipeline) {
+        for (pass, other) in self.passes.iter_mut().zip(&other.passes) {
+            pass.merge(other.as_ref());
+        }
+    }
+
+    /// Reports of the passes that have one, with the pass name.
+    pub fn reports(&self) -> Vec<(&str, String)> {
+        self.passes
+            .iter()
+            .filter_map(|p| p.report().map(|r| (p.name(), r)))
+            .collect()
+    }
+}
+
+#[cfg(feature = "config")]
+pub(crate) use config::h1_level;
+#[cfg(feature = "config")]
+pub use config::{MatchKind, PassConfig, PassEntry, PipelineConfig, RuleConfig};
+
+#[cfg(feature = "config")]
+mod config {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use regex::Regex;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+    use crate::{
+        config::{default_bold_to_h, default_h1_level},
+        title::normalize,
+        visitor::{
+            link_rewrite::RenameRule,
+            markdown::{BoldMapping, H1_LEVELS},
+        },
+    };
+
+    /// Pipeline description, usually read from TOML:
+    ///
+    /// ```toml
+    /// [[pass]]
+    /// name = "headings"
+    /// h1_level = 3
+    /// bold_to_h = true
+    /// # or an explicit table: levels = { 3 = { heading = 2 }, 1 = "bold" }
+    /// # or derived from each page: auto = true
+    ///
+    /// [[pass]]
+    /// name = "strip_tags"
+    /// enabled = false
+    /// tags = ["draft"]
+    ///
+    /// [[pass]]
+    /// name = "rewrite_links"
+    /// rules = [
+    ///     { from = "Old title", to = "New title" },
+    ///     { from = "API", to = "Api", match = "exact" },
+    ///     { from = "^/project/(.*)$", to = "$1", match = "regex" },
+    /// ]
+    /// ```
+    ///
+    /// Passes run in the order they are listed; disabled passes are skipped.
+    #[derive(Debug, Default, Clone, Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct PipelineConfig {
+        #[serde(default, rename = "pass")]
+        pub passes: Vec<PassEntry>,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct PassEntry {
+        pub enabled: bool,
+        #[serde(flatten)]
+        pub pass: PassConfig,
+    }
+
+    fn enabled() -> bool {
+        true
+    }
+
+    /// `enabled` sits next to the options of the pass, which serde's
+    /// `flatten` would read without rejecting unknown keys. The options are
+    /// therefore collected first and read as a `PassConfig` on their own.
+    impl<'de> Deserialize<'de> for PassEntry {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            #[derive(Deserialize)]
+            struct Entry {
+                #[serde(default = "enabled")]
+                enabled: bool,
+                #[serde(flatten)]
+                options: serde_json::Map<String, serde_json::Value>,
+            }
+
+            let entry = Entry::deserialize(deserializer)?;
+            let pass = PassConfig::deserialize(serde_json::Value::Object(entry.options))
+                .map_err(D::Error::custom)?;
+            Ok(Self {
+                enabled: entry.enabled,
+                pass,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize, Serialize)]
+    #[serde(tag = "name", rename_all = "snake_case", deny_unknown_fields)]
+    pub enum PassConfig {
+        Headings {
+            #[serde(default = "default_h1_level", deserialize_with = "h1_level")]
+            h1_level: u8,
+            #[serde(default = "default_bold_to_h")]
+            bold_to_h: bool,
+            /// Explicit bold level table, replacing `h1_level` and `bold_to_h`.
+            #[serde(
+                default,
+                deserialize_with = "bold_levels",
+                serialize_with = "serialize_bold_levels",
+                skip_serializing_if = "Option::is_none"
+            )]
+            levels: Option<BTreeMap<u8, BoldMapping>>,
+            #[serde(default)]
+            auto: bool,
+        },
+        StripTags {
+            #[serde(default)]
+            tags: Vec<String>,
+        },
+        RewriteLinks {
+            #[serde(default)]
+            rules: Vec<RuleConfig>,
+            /// Titles that exist in the target; other links are reported.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            known: Option<BTreeSet<String>>,
+        },
+    }
+
+    #[derive(Debug, Clone, Deserialize, Serialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct RuleConfig {
+        pub from: String,
+        pub to: String,
+        #[serde(default, rename = "match")]
+        pub kind: MatchKind,
+    }
+
+    #[derive(Debug, Default, Clone, Deserialize, Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum MatchKind {
+        Exact,
+        /// Any spelling of the same page, as Scrapbox compares titles.
+        #[default]
+        CaseInsensitive,
+        Regex,
+    }
+
+    impl RuleConfig {
+        fn rule(&self) -> Result<RenameRule, regex::Error> {
+            let (from, to) = (self.from.clone(), self.to.clone());
+            Ok(match self.kind {
+                MatchKind::Exact => RenameRule::Exact { from, to },
+                MatchKind::CaseInsensitive => RenameRule::CaseInsensitive { from, to },
+                MatchKind::Regex => RenameRule::Regex {
+                    from: Regex::new(&from)?,
+                    to,
+                },
+            })
+        }
+    }
+
+    /// Rejects bold levels outside `H1_LEVELS`, which would promote lines to
+    /// headings Markdown does not have.
+    pub(crate) fn h1_level<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+        let level = u8::deserialize(deserializer)?;
+        if !H1_LEVELS.contains(&level) {
+            return Err(D::Error::custom(format!(
+                "h1_level {} is not between 1 and 6",
+                level
+            )));
+        }
+        Ok(level)
+    }
+
+    /// TOML keys are strings, so the bold levels are parsed here.
+    fn bold_levels<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<BTreeMap<u8, BoldMapping>>, D::Error> {
+        let levels: Option<BTreeMap<String, BoldMapping>> = Option::deserialize(deserializer)?;
+        levels
+            .map(|levels| {
+                levels
+                    .into_iter()
+                    .map(|(bold, mapping)| {
+                        let bold: u8 = bold.parse().map_err(D::Error::custom)?;
+                        if bold == 0 {
+                            return Err(D::Error::custom("bold level 0 does not exist"));
+                        }
+                        if let BoldMapping::Heading(level) = mapping {
+                            if !(1..=6).contains(&level) {
+                                return Err(D::Error::custom(format!(
+                                    "heading level {} is not between 1 and 6",
+                                    level
+                                )));
+                            }
+                        }
+                        Ok((bold, mapping))
+                    })
+                    .collect()
+            })
+            .transpose()
+    }
+
+    fn serialize_bold_levels<S: Serializer>(
+        levels: &Option<BTreeMap<u8, BoldMapping>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        levels
+            .as_ref()
+            .map(|levels| {
+                levels
+                    .iter()
+                    .map(|(bold, mapping)| (bold.to_string(), mapping))
+                    .collect::<BTreeMap<_, _>>()
+            })
+            .serialize(serializer)
+    }
+
+    impl PipelineConfig {
+        pub fn from_toml(input: &str) -> Result<Self, toml::de::Error> {
+            toml::from_str(input)
+        }
+
+        pub fn build(&self) -> Result<Pipeline, regex::Error> {
+            let mut pipeline = Pipeline::new();
+            for entry in self.passes.iter().filter(|e| e.enabled) {
+                pipeline = match &entry.pass {
+                    PassConfig::Headings {
+                        h1_level,
+                        bold_to_h,
+                        levels,
+                        auto,
+                    } => {
+                        let mut pass = MarkdownPass::from_levels(*h1_level, *bold_to_h);
+                        if let Some(levels) = levels {
+                            pass.levels = levels.clone();
+                        }
+                        pass.auto = *auto;
+                        pipeline.with(pass)
+                    }
+                    PassConfig::StripTags { tags } => {
+                        pipeline.with(StripTagsPass { tags: tags.clone() })
+                    }
+                    PassConfig::RewriteLinks { rules, known } => {
+                        let mut pass = LinkRewritePass::new(
+                            rules
+                                .iter()
+                                .map(RuleConfig::rule)
+                                .collect::<Result<_, _>>()?,
+                        );
+                        pass.known = known
+                            .as_ref()
+                            .map(|known| known.iter().map(|t| normalize(t)).collect());
+                        pipeline.with(pass)
+                    }
+                };
+            }
+            Ok(pipeline)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn from_toml_test() {
+            let config = PipelineConfig::from_toml(
+                r#"
+                [[pass]]
+                name = "strip_tags"
+
+                [[pass]]
+                name = "headings"
+                enabled = false
+
+                [[pass]]
+                name = "headings"
+                levels = { 3 = { heading = 2 }, 1 = "bold" }
+
+                [[pass]]
+                name = "rewrite_links"
+                rules = [{ from = "a", to = "b", match = "case_insensitive" }]
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                config.build().unwrap().names(),
+                vec!["strip_tags", "headings", "rewrite_links"]
+            );
+
+            assert!(PipelineConfig::from_toml("[[pass]]\nname = \"unknown\"").is_err());
+            for typo in ["h1_levle = 2", "enable = false"] {
+                let toml = format!("[[pass]]\nname = \"headings\"\n{}", typo);
+                assert!(PipelineConfig::from_toml(&toml).is_err(), "{}", typo);
+            }
+            assert!(
+                PipelineConfig::from_toml("[[pass]]\nname = \"headings\"\nh1_level = 255").is_err()
+            );
+            assert!(PipelineConfig::from_toml(
+                "[[pass]]\nname = \"headings\"\nlevels = { 3 = { heading = 7 } }"
+            )
+            .is_err());
+            assert!(PipelineConfig::from_toml(
+                "[[pass]]\nname = \"headings\"\nlevels = { 0 = \"bold\" }"
+            )
+            .is_err());
+        }
+    }
+}
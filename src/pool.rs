@@ -0,0 +1,174 @@
+//! Scoped thread pool that hands results back in order.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Mutex,
+    },
+    thread,
+};
+
+/// Runs `f` over `items` on `jobs` threads and hands each result to `done`
+/// on the calling thread, in the order of `items`.
+///
+/// Once `done` fails, no more items are started and its error is returned.
+pub(crate) fn for_each_ordered<'a, T: Sync, R: Send, E>(
+    items: &'a [T],
+    jobs: usize,
+    f: impl Fn(&'a T) -> R + Sync,
+    done: impl FnMut(R) -> Result<(), E>,
+) -> Result<(), E> {
+    let workers = vec![(); jobs.clamp(1, items.len().max(1))];
+    for_each_ordered_with(items, workers, |_, item| f(item), done).map(drop)
+}
+
+/// Like `for_each_ordered`, with one thread per element of `workers`, whose
+/// thread hands it to `f` with every item. Returns the workers once all
+/// items are done.
+pub(crate) fn for_each_ordered_with<I, W, R, E>(
+    items: I,
+    mut workers: Vec<W>,
+    f: impl Fn(&mut W, I::Item) -> R + Sync,
+    mut done: impl FnMut(R) -> Result<(), E>,
+) -> Result<Vec<W>, E>
+where
+    I: IntoIterator,
+    I::IntoIter: Send,
+    I::Item: Send,
+    W: Send,
+    R: Send,
+{
+    assert!(!workers.is_empty(), "a pool needs a worker");
+    let items = items.into_iter();
+    if workers.len() == 1 || matches!(items.size_hint().1, Some(n) if n <= 1) {
+        let worker = &mut workers[0];
+        for item in items {
+            done(f(worker, item))?;
+        }
+        return Ok(workers);
+    }
+
+    let items = Mutex::new(items.enumerate());
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|mut worker| {
+                let tx = tx.clone();
+                let (items, stop, f) = (&items, &stop, &f);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        let next = items.lock().expect("no worker panics holding it").next();
+                        let Some((i, item)) = next else { break };
+                        if tx.send((i, f(&mut worker, item))).is_err() {
+                            break;
+                        }
+                    }
+                    worker
+                })
+            })
+            .collect();
+        drop(tx);
+
+        // Results arrive in any order; hold them back until their turn.
+        let mut pending = BTreeMap::new();
+        let mut turn = 0;
+        for (i, result) in rx {
+            pending.insert(i, result);
+            while let Some(result) = pending.remove(&turn) {
+                turn += 1;
+                if let Err(e) = done(result) {
+                    stop.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(handles
+            .into_iter()
+            .map(|h| h.join().expect("worker panicked"))
+            .collect())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::atomic::AtomicUsize, time::Duration};
+
+    #[test]
+    fn order_test() {
+        let items: Vec<u64> = (0..100).collect();
+        let mut results = vec![];
+        for_each_ordered(
+            &items,
+            8,
+            |&i| {
+                // Later items finish first.
+                thread::sleep(Duration::from_micros(100 - i));
+                i
+            },
+            |i| {
+                results.push(i);
+                Ok::<_, ()>(())
+            },
+        )
+        .unwrap();
+        assert_eq!(results, items);
+    }
+
+    #[test]
+    fn stop_test() {
+        let items: Vec<usize> = (0..1000).collect();
+        let started = AtomicUsize::new(0);
+        let mut seen = vec![];
+        let result = for_each_ordered(
+            &items,
+            4,
+            |&i| {
+                started.fetch_add(1, Ordering::Relaxed);
+                thread::sleep(Duration::from_micros(50));
+                i
+            },
+            |i| {
+                seen.push(i);
+                if i == 10 {
+                    Err(i)
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert_eq!(result, Err(10));
+        assert_eq!(seen, (0..=10).collect::<Vec<_>>());
+        assert!(started.load(Ordering::Relaxed) < items.len());
+    }
+
+    #[test]
+    fn workers_test() {
+        let workers = for_each_ordered_with(
+            0..100usize,
+            vec![0; 4],
+            |sum: &mut usize, i| *sum += i,
+            |_| Ok::<_, ()>(()),
+        )
+        .unwrap();
+        assert_eq!(workers.len(), 4);
+        assert_eq!(workers.iter().sum::<usize>(), (0..100).sum::<usize>());
+        // Without an upper bound on the number of items, they are still shared
+        // out.
+        let workers = for_each_ordered_with(
+            (0..100usize).filter(|i| i % 2 == 0),
+            vec![0; 4],
+            |count: &mut usize, _| {
+                *count += 1;
+                thread::sleep(Duration::from_micros(100));
+            },
+            |_| Ok::<_, ()>(()),
+        )
+        .unwrap();
+        assert_eq!(workers.iter().sum::<usize>(), 50);
+        assert!(workers.iter().filter(|&&count| count > 0).count() > 1);
+    }
+}
@@ -0,0 +1,675 @@
+//! Converting a whole project.
+//!
+//! Page files are read into [`Source`]s, parsed with [`load_pages`],
+//! transformed by [`run_passes`] and written with [`write_dir`], one file per
+//! page, or [`write_to`], one page after the other:
+//!
+//! ```no_run
+//! use scrapbox_converter::{
+//!     project::{self, ConvertOptions, Source},
+//!     Config,
+//! };
+//!
+//! # fn main() -> Result<(), project::Error> {
+//! let config = Config::default();
+//! let options = ConvertOptions::default();
+//! let sources = project::expand("pages", None)?
+//!     .into_iter()
+//!     .map(|path| Source::read(path, None))
+//!     .collect::<Result<Vec<_>, _>>()?;
+//! let mut pages = project::load_pages(&sources, options.jobs)?;
+//! project::remove_duplicates(&mut pages);
+//! project::run_passes(&mut pages, &config, &options)?;
+//! project::write_dir(&pages, &config, &options, "out".as_ref(), |_| {})?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    error, fmt, fs,
+    io::{self, BufWriter},
+    mem,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    cache::{self, Manifest},
+    config::Config,
+    graph::{LinkGraph, Related},
+    parse_markdown, parse_page,
+    parser::markdown::MarkdownReaderConfig,
+    pool::{for_each_ordered, for_each_ordered_with},
+    title,
+    visitor::{
+        logseq::{LogseqGen, LogseqGenConfig},
+        markdown::MarkdownGen,
+        org::{OrgGen, OrgGenConfig},
+        plain_text::{PlainTextGen, PlainTextGenConfig},
+        scrapbox::{ScrapboxGen, ScrapboxGenConfig},
+    },
+    Page, PageMeta, ParseError,
+};
+
+/// Error of reading, parsing or writing the pages of a project.
+#[derive(Debug)]
+pub struct Error {
+    path: Option<PathBuf>,
+    source: Box<dyn error::Error + Send + Sync>,
+}
+
+impl Error {
+    fn new(source: impl Into<Box<dyn error::Error + Send + Sync>>) -> Self {
+        Self {
+            path: None,
+            source: source.into(),
+        }
+    }
+
+    /// Blames the error on `path` unless it already names a file.
+    fn at(mut self, path: &Path) -> Self {
+        self.path.get_or_insert_with(|| path.to_path_buf());
+        self
+    }
+
+    /// The file the error is about, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path.display(), self.source),
+            None => self.source.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+macro_rules! from_error {
+    ($($error:ty),*) => {
+        $(impl From<$error> for Error {
+            fn from(e: $error) -> Self {
+                Self::new(e)
+            }
+        })*
+    };
+}
+
+from_error!(
+    io::Error,
+    ParseError,
+    regex::Error,
+    glob::PatternError,
+    glob::GlobError
+);
+#[cfg(feature = "serde")]
+from_error!(serde_json::Error);
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Format pages are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InputFormat {
+    Scrapbox,
+    Markdown,
+    /// The syntax tree as written by [`OutputFormat::Json`].
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+impl InputFormat {
+    /// The format of a page file, by its extension. Markdown is only read
+    /// when asked for, as `.md` files are more often converted pages than
+    /// pages to convert.
+    pub fn of(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("txt") => Some(InputFormat::Scrapbox),
+            #[cfg(feature = "serde")]
+            Some("json") => Some(InputFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Extension of page files in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            InputFormat::Scrapbox => "txt",
+            InputFormat::Markdown => "md",
+            #[cfg(feature = "serde")]
+            InputFormat::Json => "json",
+        }
+    }
+}
+
+/// Format pages are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum OutputFormat {
+    #[default]
+    Markdown,
+    Logseq,
+    Org,
+    Text,
+    Scrapbox,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown | OutputFormat::Logseq => "md",
+            OutputFormat::Org => "org",
+            OutputFormat::Text | OutputFormat::Scrapbox => "txt",
+            #[cfg(feature = "serde")]
+            OutputFormat::Json => "json",
+        }
+    }
+
+    /// Whether bold lines are promoted to headings unless passes are configured.
+    pub fn has_headings(self) -> bool {
+        matches!(self, OutputFormat::Markdown | OutputFormat::Org)
+    }
+
+    /// Whether pages are written as they were read, without running passes.
+    pub fn is_raw(self) -> bool {
+        #[cfg(feature = "serde")]
+        if self == OutputFormat::Json {
+            return true;
+        }
+        false
+    }
+}
+
+/// A page file, or stdin without a path, kept in memory so that parsed pages
+/// can borrow their text from it.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub path: Option<PathBuf>,
+    pub format: InputFormat,
+    pub contents: String,
+}
+
+impl Source {
+    /// Reads the page file at `path`, in `format` or else by its extension.
+    pub fn read(path: PathBuf, format: Option<InputFormat>) -> Result<Self> {
+        let contents = fs::read_to_string(&path).map_err(|e| Error::new(e).at(&path))?;
+        let format = format
+            .or_else(|| InputFormat::of(&path))
+            .unwrap_or(InputFormat::Scrapbox);
+        Ok(Self {
+            path: Some(path),
+            format,
+            contents,
+        })
+    }
+
+    /// Parses the page and fills in its metadata from the file when it has
+    /// none.
+    pub fn parse(&self) -> Result<Page<'_>> {
+        let Some(path) = &self.path else {
+            return parse(&self.contents, self.format);
+        };
+        let mut p = parse(&self.contents, self.format).map_err(|e| e.at(path))?;
+        if p.meta == PageMeta::default() {
+            p.meta = page_meta(path);
+        }
+        Ok(p)
+    }
+}
+
+/// Reads a page from Scrapbox text, Markdown or a serialized AST.
+fn parse(contents: &str, format: InputFormat) -> Result<Page<'_>> {
+    match format {
+        InputFormat::Scrapbox => Ok(parse_page(contents)?),
+        InputFormat::Markdown => Ok(parse_markdown(contents, &MarkdownReaderConfig::default())),
+        #[cfg(feature = "serde")]
+        InputFormat::Json => Ok(serde_json::from_str(contents)?),
+    }
+}
+
+fn page_meta(path: &Path) -> PageMeta {
+    let secs = |t: io::Result<std::time::SystemTime>| {
+        t.ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+    };
+    let metadata = fs::metadata(path).ok();
+    PageMeta {
+        title: path.file_stem().map(|s| s.to_string_lossy().into_owned()),
+        created: metadata.as_ref().and_then(|m| secs(m.created())),
+        updated: metadata.as_ref().and_then(|m| secs(m.modified())),
+    }
+}
+
+/// Files named by `input`: the file itself, the pages of a directory, or the
+/// matches of a glob pattern. In a directory, pages are the files in `from`,
+/// or else the files `InputFormat::of` knows.
+pub fn expand(input: &str, from: Option<InputFormat>) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    let mut paths: Vec<PathBuf> = if path.is_dir() {
+        fs::read_dir(path)?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|p| is_page(p, from))
+            .collect()
+    } else if path.exists() {
+        vec![path.to_path_buf()]
+    } else {
+        glob::glob(input)?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|p| p.is_file())
+            .collect()
+    };
+    if paths.is_empty() {
+        return Err(Error::new(format!("no pages found for {input}")));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Whether `path` has the extension of a page file in `from`, or else of any
+/// format `InputFormat::of` knows.
+pub fn is_page(path: &Path, from: Option<InputFormat>) -> bool {
+    match from {
+        Some(format) => path.extension() == Some(format.extension().as_ref()),
+        None => InputFormat::of(path).is_some(),
+    }
+}
+
+/// Parses `sources` on `jobs` threads, keeping their order.
+pub fn load_pages(sources: &[Source], jobs: usize) -> Result<Vec<Page<'_>>> {
+    let mut pages = Vec::with_capacity(sources.len());
+    for_each_ordered(sources, jobs, Source::parse, |p| -> Result<()> {
+        pages.push(p?);
+        Ok(())
+    })?;
+    Ok(pages)
+}
+
+/// Drops pages whose title is the same as that of an earlier page, returning
+/// the title of each dropped page with the one it duplicates.
+pub fn remove_duplicates(pages: &mut Vec<Page>) -> Vec<(String, String)> {
+    let mut seen: HashMap<String, String> = HashMap::new();
+    let mut duplicates = vec![];
+    pages.retain(|p| match seen.entry(title::normalize(&page_title(p))) {
+        Entry::Occupied(e) => {
+            duplicates.push((page_title(p), e.get().clone()));
+            false
+        }
+        Entry::Vacant(e) => {
+            e.insert(page_title(p));
+            true
+        }
+    });
+    duplicates
+}
+
+/// The title of `p`; pages read from stdin are called `stdin`.
+pub fn page_title(p: &Page) -> String {
+    p.meta.title.clone().unwrap_or_else(|| "stdin".to_string())
+}
+
+/// Links between `pages`.
+pub fn link_graph(pages: &[Page]) -> LinkGraph {
+    let mut graph = LinkGraph::new();
+    for p in pages {
+        graph.add(&page_title(p), p);
+    }
+    graph
+}
+
+/// Runs the passes `config` sets up for the output format over `pages`, on
+/// `options.jobs` threads, returning the reports of the passes that have one,
+/// with the pass name.
+pub fn run_passes(
+    pages: &mut [Page],
+    config: &Config,
+    options: &ConvertOptions,
+) -> Result<Vec<(String, String)>> {
+    if options.format.is_raw() {
+        return Ok(vec![]);
+    }
+    // Every thread runs its own pipeline; their findings are merged after.
+    let pipelines = (0..options.jobs.clamp(1, pages.len().max(1)))
+        .map(|_| config.pipeline(options.format.has_headings()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let taken: Vec<_> = pages.iter_mut().map(mem::take).collect();
+    let mut slots = pages.iter_mut();
+    let pipelines = for_each_ordered_with(
+        taken,
+        pipelines,
+        |pipeline, mut p| {
+            pipeline.run(&mut p);
+            p
+        },
+        |p| {
+            *slots.next().expect("one page per slot") = p;
+            Ok::<_, Error>(())
+        },
+    )?;
+
+    let mut pipelines = pipelines.into_iter();
+    let mut pipeline = pipelines.next().expect("a pool has a worker");
+    for other in pipelines {
+        pipeline.merge(&other);
+    }
+    Ok(pipeline
+        .reports()
+        .into_iter()
+        .map(|(name, report)| (name.to_string(), report))
+        .collect())
+}
+
+/// How pages are written by [`write_dir`] and [`write_to`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ConvertOptions {
+    pub format: OutputFormat,
+    /// Also write a page for every hashtag and linked title without one.
+    pub stubs: bool,
+    /// Rewrite every file, even those the cache manifest has up to date.
+    pub force: bool,
+    /// Number of pages generated at once.
+    pub jobs: usize,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::default(),
+            stubs: false,
+            force: false,
+            jobs: 1,
+        }
+    }
+}
+
+/// A page handed to the callback of [`write_dir`] or [`write_to`], in the
+/// order of the pages.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Written<'a> {
+    pub title: &'a str,
+    /// Whether the output was written, rather than left as it was.
+    pub changed: bool,
+    /// Whether the page is a stub for a title without a page.
+    pub stub: bool,
+}
+
+/// Writes `pages` to `writer`, each followed by an empty line.
+pub fn write_to(
+    pages: &[Page],
+    config: &Config,
+    options: &ConvertOptions,
+    mut writer: impl io::Write,
+    mut on_page: impl FnMut(Written),
+) -> Result<()> {
+    let graph = link_graph(pages);
+    // Pages are generated into buffers so that they can be written in order.
+    for_each_ordered(
+        pages,
+        options.jobs,
+        |p| {
+            let title = page_title(p);
+            let mut buf = vec![];
+            generate(options.format, config, p, graph.related(&title), &mut buf)?;
+            Ok((title, buf))
+        },
+        |result: Result<_>| {
+            let (title, buf) = result?;
+            writer.write_all(&buf)?;
+            writeln!(writer)?;
+            on_page(Written {
+                title: &title,
+                changed: true,
+                stub: false,
+            });
+            Ok(())
+        },
+    )
+}
+
+/// Writes `pages` into `out`, one file per page.
+///
+/// A cache manifest in `out` records what every file was generated from, so
+/// files whose page, settings and backlinks are unchanged are left alone.
+pub fn write_dir(
+    pages: &[Page],
+    config: &Config,
+    options: &ConvertOptions,
+    out: &Path,
+    mut on_page: impl FnMut(Written),
+) -> Result<()> {
+    let graph = link_graph(pages);
+    fs::create_dir_all(out)?;
+    let cache = if options.force {
+        Manifest::new()
+    } else {
+        Manifest::load(out)?
+    };
+    // Everything besides the page itself that the written file depends on,
+    // including the converter, which may write the same page differently.
+    let settings = format!(
+        "{} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        options.format,
+        config
+    );
+    // Writes a page unless the cache has it, returning its file and manifest entry.
+    let write =
+        |title: &str, p: &Page, related: Related| -> Result<(PathBuf, cache::Entry, bool)> {
+            let file_name = PathBuf::from(format!(
+                "{}.{}",
+                title::file_name(title),
+                options.format.extension()
+            ));
+            let entry = cache::Entry {
+                input: cache::hash(p),
+                config: cache::hash(&(&settings, &related)),
+            };
+            if cache.is_fresh(out, &file_name, entry) {
+                return Ok((file_name, entry, false));
+            }
+
+            let path = out.join(&file_name);
+            let file = fs::File::create(&path).map_err(|e| Error::new(e).at(&path))?;
+            generate(options.format, config, p, related, BufWriter::new(file))
+                .map_err(|e| Error::new(e).at(&path))?;
+            Ok((file_name, entry, true))
+        };
+
+    let mut manifest = Manifest::new();
+    for_each_ordered(
+        pages,
+        options.jobs,
+        |p| {
+            let title = page_title(p);
+            let written = write(&title, p, graph.related(&title))?;
+            Ok((title, written))
+        },
+        |result: Result<_>| -> Result<()> {
+            let (title, (file_name, entry, changed)) = result?;
+            manifest.insert(file_name, entry);
+            on_page(Written {
+                title: &title,
+                changed,
+                stub: false,
+            });
+            Ok(())
+        },
+    )?;
+    if options.stubs {
+        let missing: Vec<_> = graph.missing().collect();
+        for_each_ordered(
+            &missing,
+            options.jobs,
+            |title| {
+                let written = write(title, &graph.stub(title), Related::default())?;
+                Ok((title, written))
+            },
+            |result: Result<_>| -> Result<()> {
+                let (title, (file_name, entry, changed)) = result?;
+                manifest.insert(file_name, entry);
+                on_page(Written {
+                    title,
+                    changed,
+                    stub: true,
+                });
+                Ok(())
+            },
+        )?;
+    }
+    manifest.save(out)?;
+    Ok(())
+}
+
+/// Writes `p` in `format` straight to `writer`.
+pub fn generate(
+    format: OutputFormat,
+    config: &Config,
+    p: &Page,
+    related: Related,
+    writer: impl io::Write,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Markdown => MarkdownGen::with_writer(config.markdown_gen(), writer)
+            .with_related(related)
+            .write(p),
+        OutputFormat::Logseq => LogseqGen::with_writer(LogseqGenConfig::default(), writer).write(p),
+        OutputFormat::Org => OrgGen::with_writer(OrgGenConfig::default(), writer).write(p),
+        OutputFormat::Text => {
+            PlainTextGen::with_writer(PlainTextGenConfig::default(), writer).write(p)
+        }
+        OutputFormat::Scrapbox => {
+            let scrapbox = ScrapboxGenConfig {
+                h1_level: config.headings.h1_level,
+            };
+            ScrapboxGen::with_writer(scrapbox, writer).write(p)
+        }
+        #[cfg(feature = "serde")]
+        OutputFormat::Json => {
+            let mut writer = writer;
+            serde_json::to_writer_pretty(&mut writer, p)?;
+            writer.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(title: &str, text: &'static str) -> Page<'static> {
+        let mut p = parse_page(text).unwrap();
+        p.meta.title = Some(title.to_string());
+        p
+    }
+
+    #[test]
+    fn backlink_test() {
+        let out = std::env::temp_dir().join(format!("scrapbox-converter-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out);
+        let (config, options) = (Config::default(), ConvertOptions::default());
+        let changed = |pages: &[Page]| {
+            let mut changed = vec![];
+            write_dir(pages, &config, &options, &out, |w| {
+                if w.changed {
+                    changed.push(w.title.to_string());
+                }
+            })
+            .unwrap();
+            changed
+        };
+
+        assert_eq!(changed(&[page("a", "x"), page("b", "y")]), ["a", "b"]);
+        assert!(changed(&[page("a", "x"), page("b", "y")]).is_empty());
+        // `a` gets a backlink, which its "Linked from" section lists.
+        assert_eq!(changed(&[page("a", "x"), page("b", "[a]")]), ["a", "b"]);
+        fs::remove_dir_all(&out).unwrap();
+    }
+
+    #[test]
+    fn expand_test() {
+        let dir =
+            std::env::temp_dir().join(format!("scrapbox-converter-expand-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        for name in ["a.txt", "b.md", "c.png"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let input = dir.to_str().unwrap();
+        let names = |from| -> Vec<_> {
+            expand(input, from)
+                .unwrap()
+                .iter()
+                .map(|p| p.file_name().unwrap().to_owned())
+                .collect()
+        };
+
+        // Converted pages in a project directory are not read back.
+        assert_eq!(names(None), ["a.txt"]);
+        assert_eq!(names(Some(InputFormat::Markdown)), ["b.md"]);
+        assert!(expand(input, Some(InputFormat::Scrapbox)).is_ok());
+        assert!(is_page(Path::new("x/b.md"), Some(InputFormat::Markdown)));
+        assert!(!is_page(Path::new("x/b.md"), None));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn run_passes_test() {
+        let config = Config::from_toml(
+            r#"
+            [[pass]]
+            name = "rewrite_links"
+            rules = [{ from = "Old", to = "New" }]
+            known = ["New"]
+            "#,
+        )
+        .unwrap();
+        let sources: Vec<_> = (0..20)
+            .map(|i| Source {
+                path: None,
+                format: InputFormat::Scrapbox,
+                contents: format!("[Old] [Missing {}]", i % 3),
+            })
+            .collect();
+        let options = ConvertOptions {
+            jobs: 4,
+            ..Default::default()
+        };
+
+        let mut pages = load_pages(&sources, options.jobs).unwrap();
+        let reports = run_passes(&mut pages, &config, &options).unwrap();
+        let mut out = vec![];
+        write_to(&pages[..2], &config, &options, &mut out, |_| {}).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[New](new.md) [Missing 0](missing_0.md)\n\n[New](new.md) [Missing 1](missing_1.md)\n\n"
+        );
+        assert_eq!(
+            reports,
+            vec![(
+                "rewrite_links".to_string(),
+                "rule 1 (Old (any case) -> New): 20 links\n\
+                 unresolved: Missing 0\n\
+                 unresolved: Missing 1\n\
+                 unresolved: Missing 2\n"
+                    .to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::{cmp::Reverse, collections::BTreeMap, fmt};
+
+use crate::{
+    graph::{LinkGraph, LinkKind},
+    title::normalize,
+};
+
+/// An internal link to a page that does not exist, a Scrapbox "empty link".
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BrokenLink {
+    pub source: String,
+    /// One-based line number in the source page.
+    pub line: usize,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MissingPage {
+    pub title: String,
+    /// Number of pages linking to the title.
+    pub linked_from: usize,
+}
+
+/// Link health of a project.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LinkReport {
+    pub broken_links: Vec<BrokenLink>,
+    /// Pages that no link or hashtag points at.
+    pub orphan_pages: Vec<String>,
+    /// Missing titles, most linked first.
+    pub missing_pages: Vec<MissingPage>,
+}
+
+impl LinkReport {
+    /// Hashtags are allowed to point at missing pages, so only bracket links
+    /// count as broken; both keep a page from being an orphan.
+    pub fn new(graph: &LinkGraph) -> Self {
+        let mut report = Self::default();
+
+        for page in graph.pages() {
+            for link in graph.links(page) {
+                if link.kind == LinkKind::Link && !graph.contains(&link.target) {
+                    report.broken_links.push(BrokenLink {
+                        source: page.to_string(),
+                        line: link.line + 1,
+                        target: link.target.clone(),
+                    });
+                }
+            }
+            if graph.backlinks(page).is_empty() {
+                report.orphan_pages.push(page.to_string());
+            }
+        }
+
+        // One entry per page, spelled as it is first linked.
+        let mut missing = BTreeMap::new();
+        for link in &report.broken_links {
+            missing
+                .entry(normalize(&link.target))
+                .or_insert(link.target.as_str());
+        }
+        report.missing_pages = missing
+            .into_values()
+            .map(|title| MissingPage {
+                title: title.to_string(),
+                linked_from: graph.backlinks(title).len(),
+            })
+            .collect();
+        report
+            .missing_pages
+            .sort_by_key(|page| Reverse(page.linked_from));
+
+        report
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.broken_links.is_empty()
+    }
+}
+
+impl fmt::Display for LinkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Broken links: {}", self.broken_links.len())?;
+        for link in &self.broken_links {
+            writeln!(f, "  {}:{}: [{}]", link.source, link.line, link.target)?;
+        }
+        writeln!(f, "Orphan pages: {}", self.orphan_pages.len())?;
+        for page in &self.orphan_pages {
+            writeln!(f, "  {}", page)?;
+        }
+        writeln!(f, "Missing pages: {}", self.missing_pages.len())?;
+        for page in &self.missing_pages {
+            writeln!(f, "  {} ({} pages)", page.title, page.linked_from)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::page;
+
+    #[test]
+    fn report_test() {
+        let mut graph = LinkGraph::new();
+        for (title, text) in [
+            ("a", "[b] #tag\n[gone]\n"),
+            ("b", "[Gone] [a]\n"),
+            ("c", "[void] #a\n"),
+        ] {
+            graph.add(title, &page(text).unwrap().1);
+        }
+        let report = LinkReport::new(&graph);
+
+        assert_eq!(
+            report.broken_links,
+            vec![
+                BrokenLink {
+                    source: "a".to_string(),
+                    line: 2,
+                    target: "gone".to_string(),
+                },
+                BrokenLink {
+                    source: "b".to_string(),
+                    line: 1,
+                    target: "Gone".to_string(),
+                },
+                BrokenLink {
+                    source: "c".to_string(),
+                    line: 1,
+                    target: "void".to_string(),
+                },
+            ]
+        );
+        assert_eq!(report.orphan_pages, vec!["c"]);
+        assert_eq!(report.missing_pages.len(), 2);
+        assert_eq!(report.missing_pages[0].title, "gone");
+        assert_eq!(report.missing_pages[0].linked_from, 2);
+    }
+
+    #[test]
+    fn code_block_line_test() {
+        let mut graph = LinkGraph::new();
+        let text = "intro\ncode:x.sh\n ls\n \n\t[in code]\n[missing]\n";
+        graph.add("a", &page(text).unwrap().1);
+        let report = LinkReport::new(&graph);
+        assert_eq!(report.broken_links.len(), 1);
+        assert_eq!(report.broken_links[0].line, 6);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{fmt, ops::AddAssign};
+
+use crate::{
+    ast::{ExternalLink, HashTag, InternalLink, Line, LineKind, Page},
+    visitor::Visit,
+};
+
+/// Counts of the syntax used by one or more pages.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Stats {
+    pub pages: usize,
+    pub lines: usize,
+    pub list_lines: usize,
+    pub internal_links: usize,
+    pub external_links: usize,
+    pub hashtags: usize,
+}
+
+impl Stats {
+    pub fn new(page: &Page) -> Self {
+        let mut stats = Self {
+            pages: 1,
+            ..Default::default()
+        };
+        stats.visit(page);
+        stats
+    }
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.pages += rhs.pages;
+        self.lines += rhs.lines;
+        self.list_lines += rhs.list_lines;
+        self.internal_links += rhs.internal_links;
+        self.external_links += rhs.external_links;
+        self.hashtags += rhs.hashtags;
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pages, {} lines ({} list items), {} internal links, {} external links, {} hashtags",
+            self.pages,
+            self.lines,
+            self.list_lines,
+            self.internal_links,
+            self.external_links,
+            self.hashtags
+        )
+    }
+}
+
+impl Visit for Stats {
+    fn visit_line(&mut self, value: &Line) {
+        self.lines += 1;
+        if let LineKind::List(_) = value.kind {
+            self.list_lines += 1;
+        }
+        for item in value.values.iter() {
+            self.visit_syntax(item);
+        }
+    }
+
+    fn visit_hashtag(&mut self, _value: &HashTag) {
+        self.hashtags += 1;
+    }
+
+    fn visit_bracket_internal_link(&mut self, _value: &InternalLink) {
+        self.internal_links += 1;
+    }
+
+    fn visit_bracket_external_link(&mut self, _value: &ExternalLink) {
+        self.external_links += 1;
+    }
+}
@@ -0,0 +1,139 @@
+//! Page identity as Scrapbox sees it.
+//!
+//! Scrapbox treats `Foo Bar`, `foo bar` and `Foo_Bar` as the same page. Use
+//! `normalize` whenever titles are compared and `file_name` whenever a title
+//! becomes a path, so every spelling of a title ends up in the same file;
+//! links to that file use `link_path`.
+
+/// Key under which equivalent titles compare equal: trimmed, lowercase, and
+/// with underscores read as spaces.
+pub fn normalize(title: &str) -> String {
+    title.trim().replace('_', " ").to_lowercase()
+}
+
+/// File name (without extension) for the page `title`.
+pub fn file_name(title: &str) -> String {
+    let mut name = String::new();
+    for c in normalize(title).chars() {
+        match c {
+            ' ' => name.push('_'),
+            '%' | '/' | '\\' | '#' | '?' | ':' => name.push_str(&format!("%{:02X}", c as u32)),
+            c => name.push(c),
+        }
+    }
+    name
+}
+
+/// `file_name` as it goes into a relative link URL.
+///
+/// Readers percent-decode link URLs before opening the file, so the `%` of
+/// the escapes in `file_name` is escaped again, as are the parentheses that
+/// would end a Markdown link.
+pub fn link_path(title: &str) -> String {
+    let mut path = String::new();
+    for c in file_name(title).chars() {
+        match c {
+            '%' | '(' | ')' => path.push_str(&format!("%{:02X}", c as u32)),
+            c => path.push(c),
+        }
+    }
+    path
+}
+
+/// Path segment of the page `title` in a scrapbox.io URL: spaces become
+/// underscores, case is kept and everything but unreserved characters is
+/// percent-encoded.
+pub fn url_segment(title: &str) -> String {
+    let mut segment = String::new();
+    for b in title.trim().replace(' ', "_").bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                segment.push(b as char)
+            }
+            b => segment.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    segment
+}
+
+/// Page title of a relative link URL such as `link_path` writes: the file
+/// name without fragment or `.md` extension, percent-decoded both as a URL and
+/// as a `file_name`, with underscores read as spaces.
+pub fn from_link_path(url: &str) -> String {
+    let path = url.split(['#', '?']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let name = name.strip_suffix(".md").unwrap_or(name);
+    percent_decode(&percent_decode(name)).replace('_', " ")
+}
+
+/// Decodes `%XX` escapes, keeping anything else, invalid escapes included, as
+/// it is.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(b) if bytes[i] == b'%' => {
+                decoded.push(b);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_test() {
+        assert_eq!(normalize("Foo Bar"), "foo bar");
+        assert_eq!(normalize(" foo_bar "), "foo bar");
+        assert_eq!(normalize("FOO_BAR"), normalize("foo bar"));
+        assert_ne!(normalize("foo  bar"), normalize("foo bar"));
+    }
+
+    #[test]
+    fn file_name_test() {
+        assert_eq!(file_name("Foo Bar"), "foo_bar");
+        assert_eq!(file_name("Foo_Bar"), "foo_bar");
+        assert_eq!(file_name("a/b #1?"), "a%2Fb_%231%3F");
+        assert_eq!(file_name("日本語版"), "日本語版");
+    }
+
+    #[test]
+    fn link_path_test() {
+        assert_eq!(link_path("Foo Bar"), "foo_bar");
+        assert_eq!(link_path("a/b"), "a%252Fb");
+        assert_eq!(link_path("f(x)"), "f%28x%29");
+        assert_eq!(link_path("100%"), "100%2525");
+    }
+
+    #[test]
+    fn from_link_path_test() {
+        for title in ["foo bar", "a/b", "f(x)", "100%", "日本語版"] {
+            assert_eq!(from_link_path(&link_path(title)), title);
+        }
+        assert_eq!(from_link_path("docs/setup.md#install"), "setup");
+        assert_eq!(from_link_path("my%20page.md"), "my page");
+        assert_eq!(from_link_path("50%.md"), "50%");
+    }
+
+    #[test]
+    fn url_segment_test() {
+        assert_eq!(url_segment("Some Page"), "Some_Page");
+        assert_eq!(url_segment("a/b?#"), "a%2Fb%3F%23");
+        assert_eq!(url_segment("f(x)"), "f%28x%29");
+        assert_eq!(url_segment("日本"), "%E6%97%A5%E6%9C%AC");
+    }
+}
@@ -0,0 +1,121 @@
+use crate::ast::{
+    BlockQuote, Bracket, BracketKind, Emphasis, ExternalLink, HashTag, Heading, InternalLink, Line,
+    Page, Syntax, SyntaxKind, Text,
+};
+
+/// Consumes the AST and rebuilds it by value.
+///
+/// Each hook returns the node to put in place of its input, so a fold can
+/// change the kind of a node (e.g. `fold_bracket` turning an emphasis into a
+/// heading) without cloning.
+pub trait Fold {
+    fn fold<'a>(&mut self, value: Page<'a>) -> Page<'a> {
+        self.fold_page(value)
+    }
+
+    fn fold_page<'a>(&mut self, value: Page<'a>) -> Page<'a> {
+        Page {
+            lines: value
+                .lines
+                .into_iter()
+                .map(|line| self.fold_line(line))
+                .collect(),
+            ..value
+        }
+    }
+
+    fn fold_line<'a>(&mut self, value: Line<'a>) -> Line<'a> {
+        Line {
+            values: value
+                .values
+                .into_iter()
+                .map(|item| self.fold_syntax(item))
+                .collect(),
+            ..value
+        }
+    }
+
+    fn fold_syntax<'a>(&mut self, value: Syntax<'a>) -> Syntax<'a> {
+        Syntax::new(match value.kind {
+            SyntaxKind::HashTag(v) => SyntaxKind::HashTag(self.fold_hashtag(v)),
+            SyntaxKind::Bracket(v) => SyntaxKind::Bracket(self.fold_bracket(v)),
+            SyntaxKind::BlockQuote(v) => SyntaxKind::BlockQuote(self.fold_block_quote(v)),
+            SyntaxKind::Text(v) => SyntaxKind::Text(self.fold_text(v)),
+        })
+    }
+
+    fn fold_hashtag<'a>(&mut self, value: HashTag<'a>) -> HashTag<'a> {
+        value
+    }
+
+    fn fold_bracket<'a>(&mut self, value: Bracket<'a>) -> Bracket<'a> {
+        Bracket::new(match value.kind {
+            BracketKind::InternalLink(v) => {
+                BracketKind::InternalLink(self.fold_bracket_internal_link(v))
+            }
+            BracketKind::ExternalLink(v) => {
+                BracketKind::ExternalLink(self.fold_bracket_external_link(v))
+            }
+            BracketKind::Emphasis(v) => BracketKind::Emphasis(self.fold_bracket_emphasis(v)),
+            BracketKind::Heading(v) => BracketKind::Heading(self.fold_bracket_heading(v)),
+        })
+    }
+
+    fn fold_bracket_internal_link<'a>(&mut self, value: InternalLink<'a>) -> InternalLink<'a> {
+        value
+    }
+
+    fn fold_bracket_external_link<'a>(&mut self, value: ExternalLink<'a>) -> ExternalLink<'a> {
+        value
+    }
+
+    fn fold_bracket_emphasis<'a>(&mut self, value: Emphasis<'a>) -> Emphasis<'a> {
+        value
+    }
+
+    fn fold_bracket_heading<'a>(&mut self, value: Heading<'a>) -> Heading<'a> {
+        value
+    }
+
+    fn fold_block_quote<'a>(&mut self, value: BlockQuote<'a>) -> BlockQuote<'a> {
+        value
+    }
+
+    fn fold_text<'a>(&mut self, value: Text<'a>) -> Text<'a> {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::LineKind;
+
+    struct Upper;
+
+    impl Fold for Upper {
+        fn fold_bracket_internal_link<'a>(&mut self, value: InternalLink<'a>) -> InternalLink<'a> {
+            InternalLink {
+                title: value.title.to_uppercase().into(),
+            }
+        }
+    }
+
+    #[test]
+    fn fold_test() {
+        let link = |title| {
+            Syntax::new(SyntaxKind::Bracket(Bracket::new(
+                BracketKind::InternalLink(InternalLink::new(title)),
+            )))
+        };
+        let page = Page {
+            lines: vec![Line::new(
+                LineKind::Normal,
+                vec![Syntax::new(SyntaxKind::Text(Text::new("see "))), link("a")],
+            )],
+            ..Default::default()
+        };
+        let page = Upper.fold(page);
+        assert_eq!(page.lines[0].values[1], link("A"));
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt,
+};
+
+use regex::Regex;
+
+use crate::{
+    ast::{Bracket, BracketKind, HashTag, InternalLink, Syntax, SyntaxKind},
+    title::normalize,
+};
+
+use super::{TransformCommand, Visitor};
+
+/// How a rename rule matches a link title.
+#[derive(Debug, Clone)]
+pub enum RenameRule {
+    /// Matches the title exactly as written.
+    Exact { from: String, to: String },
+    /// Matches every spelling Scrapbox reads as the same page, whatever its
+    /// case or underscores, compared with
+    /// [`normalize_title`](crate::normalize_title).
+    CaseInsensitive { from: String, to: String },
+    /// `to` may refer to capture groups as `$1` or `$name`.
+    Regex { from: Regex, to: String },
+}
+
+impl RenameRule {
+    fn apply(&self, title: &str) -> Option<String> {
+        match self {
+            RenameRule::Exact { from, to } => (title == from).then(|| to.clone()),
+            RenameRule::CaseInsensitive { from, to } => {
+                (normalize(title) == normalize(from)).then(|| to.clone())
+            }
+            RenameRule::Regex { from, to } => from
+                .is_match(title)
+                .then(|| from.replace(title, to.as_str()).into_owned()),
+        }
+    }
+}
+
+impl fmt::Display for RenameRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameRule::Exact { from, to } => write!(f, "{} -> {}", from, to),
+            RenameRule::CaseInsensitive { from, to } => write!(f, "{} (any case) -> {}", from, to),
+            RenameRule::Regex { from, to } => write!(f, "/{}/ -> {}", from, to),
+        }
+    }
+}
+
+/// Renames the targets of internal links, project links and hashtags.
+///
+/// The first matching rule wins. When `known` is given, links whose target,
+/// after renaming, is not in it are reported as unresolved. `known` holds
+/// titles normalized with [`normalize_title`](crate::normalize_title).
+#[derive(Default)]
+pub struct LinkRewritePass {
+    pub rules: Vec<RenameRule>,
+    pub known: Option<HashSet<String>>,
+    pub report: LinkRewriteReport,
+}
+
+#[derive(Debug, Default)]
+pub struct LinkRewriteReport {
+    /// Number of links rewritten by each rule, by rule index.
+    pub fired: BTreeMap<usize, usize>,
+    pub unresolved: BTreeSet<String>,
+}
+
+impl LinkRewriteReport {
+    /// Adds the findings of a pass with the same rules run over other pages.
+    pub fn merge(&mut self, other: &LinkRewriteReport) {
+        for (rule, count) in &other.fired {
+            *self.fired.entry(*rule).or_default() += count;
+        }
+        self.unresolved.extend(other.unresolved.iter().cloned());
+    }
+}
+
+impl LinkRewritePass {
+    pub fn new(rules: Vec<RenameRule>) -> Self {
+        Self {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    fn rename(&mut self, title: &str) -> Option<String> {
+        let renamed = self
+            .rules
+            .iter()
+            .enumerate()
+            .find_map(|(i, rule)| rule.apply(title).map(|to| (i, to)));
+        if let Some(known) = &self.known {
+            let target = renamed.as_ref().map_or(title, |(_, to)| to.as_str());
+            if !known.contains(&normalize(target)) {
+                self.report.unresolved.insert(title.to_string());
+            }
+        }
+
+        let (i, to) = renamed?;
+        *self.report.fired.entry(i).or_default() += 1;
+        Some(to)
+    }
+
+    /// Describes which rules fired and which links are unresolved.
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            let count = self.report.fired.get(&i).copied().unwrap_or_default();
+            out.push_str(&format!("rule {} ({}): {} links\n", i + 1, rule, count));
+        }
+        for title in &self.report.unresolved {
+            out.push_str(&format!("unresolved: {}\n", title));
+        }
+        out
+    }
+}
+
+impl Visitor for LinkRewritePass {
+    fn visit_hashtag<'a>(&mut self, value: &HashTag<'a>) -> Option<TransformCommand<'a>> {
+        // Hashtags cannot contain spaces; Scrapbox writes them as underscores.
+        let to = self.rename(&value.value)?.replace(' ', "_");
+        Some(TransformCommand::Replace(Syntax::new(SyntaxKind::HashTag(
+            HashTag::new(to),
+        ))))
+    }
+
+    fn visit_bracket_internal_link<'a>(
+        &mut self,
+        value: &InternalLink<'a>,
+    ) -> Option<TransformCommand<'a>> {
+        let to = self.rename(&value.title)?;
+        Some(TransformCommand::Replace(Syntax::new(SyntaxKind::Bracket(
+            Bracket::new(BracketKind::InternalLink(InternalLink::new(to))),
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::page, visitor::Visit};
+
+    #[derive(Default)]
+    struct Links(Vec<String>);
+
+    impl Visit for Links {
+        fn visit_hashtag(&mut self, value: &HashTag) {
+            self.0.push(format!("#{}", value.value));
+        }
+
+        fn visit_bracket_internal_link(&mut self, value: &InternalLink) {
+            self.0.push(value.title.to_string());
+        }
+    }
+
+    #[test]
+    fn link_rewrite_test() {
+        let (_, mut p) =
+            page("[Old] [OTHER] [/proj/Draft x] #old [Unknown] [old_title]\n").unwrap();
+        let mut pass = LinkRewritePass::new(vec![
+            RenameRule::Exact {
+                from: "Old".to_string(),
+                to: "New Name".to_string(),
+            },
+            RenameRule::CaseInsensitive {
+                from: "Old Title".to_string(),
+                to: "Title".to_string(),
+            },
+            RenameRule::CaseInsensitive {
+                from: "other".to_string(),
+                to: "Other".to_string(),
+            },
+            RenameRule::Regex {
+                from: Regex::new("^/proj/Draft (.*)$").unwrap(),
+                to: "/proj/$1".to_string(),
+            },
+        ]);
+        pass.visit(&mut p);
+
+        let mut links = Links::default();
+        links.visit(&p);
+        assert_eq!(
+            links.0,
+            vec!["New Name", "Other", "/proj/x", "#old", "Unknown", "Title"]
+        );
+        assert_eq!(
+            pass.report.fired,
+            BTreeMap::from([(0, 1), (1, 1), (2, 1), (3, 1)])
+        );
+        // Without `known`, links no rule matched are fine as they are.
+        assert!(pass.report.unresolved.is_empty());
+    }
+
+    #[test]
+    fn match_kind_test() {
+        let rename = |rule: RenameRule| {
+            ["Old", "old", "OLD", " Old ", "older"].map(|title| rule.apply(title).is_some())
+        };
+        let (from, to) = ("Old".to_string(), "New".to_string());
+        assert_eq!(
+            rename(RenameRule::Exact {
+                from: from.clone(),
+                to: to.clone()
+            }),
+            [true, false, false, false, false]
+        );
+        assert_eq!(
+            rename(RenameRule::CaseInsensitive { from, to }),
+            [true, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn known_test() {
+        let (_, mut p) = page("[Old] [new_name] [Unknown]\n").unwrap();
+        let mut pass = LinkRewritePass::new(vec![RenameRule::Exact {
+            from: "Old".to_string(),
+            to: "New Name".to_string(),
+        }]);
+        pass.known = Some(HashSet::from([normalize("New Name")]));
+        pass.visit(&mut p);
+        assert_eq!(
+            pass.report.unresolved,
+            BTreeSet::from(["Unknown".to_string()])
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::io;
+
+use crate::{
+    ast::{ExternalLink, HashTag, InternalLink, Page, Text},
+    Heading, LineKind, ListKind,
+};
+
+use super::{markdown::fence, output::Output, Visit};
+
+#[non_exhaustive]
+pub struct LogseqGenConfig {
+    pub indent: String,
+}
+
+impl Default for LogseqGenConfig {
+    fn default() -> Self {
+        Self {
+            indent: "\t".to_string(),
+        }
+    }
+}
+
+/// Emits a Logseq outline: every line becomes a `- ` block nested by its list level.
+pub struct LogseqGen<W = Vec<u8>> {
+    document: Output<W>,
+    config: LogseqGenConfig,
+}
+
+impl LogseqGen {
+    pub fn new(config: LogseqGenConfig) -> Self {
+        Self::with_writer(config, Vec::new())
+    }
+
+    pub fn generate(&mut self, page: &Page) -> String {
+        self.write(page).expect("writing to a Vec can not fail");
+        self.document.take_string()
+    }
+}
+
+impl<W: io::Write> LogseqGen<W> {
+    pub fn with_writer(config: LogseqGenConfig, writer: W) -> Self {
+        Self {
+            document: Output::new(writer),
+            config,
+        }
+    }
+
+    pub fn write(&mut self, page: &Page) -> io::Result<()> {
+        self.visit(page);
+        self.document.finish()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.document.into_inner()
+    }
+}
+
+impl<W: io::Write> Visit for LogseqGen<W> {
+    fn visit_page(&mut self, value: &Page) {
+        // Page properties must come before the first block.
+        if let Some(title) = &value.meta.title {
+            self.document.push_str(&format!("title:: {}\n", title));
+        }
+        // Logseq stores timestamps in milliseconds.
+        if let Some(created) = value.meta.created {
+            self.document
+                .push_str(&format!("created-at:: {}\n", created * 1000));
+        }
+        if let Some(updated) = value.meta.updated {
+            self.document
+                .push_str(&format!("updated-at:: {}\n", updated * 1000));
+        }
+
+        for line in value.lines.iter() {
+            let level = match &line.kind {
+                LineKind::List(list) => list.level,
+                LineKind::CodeBlock(code) => code.level,
+                LineKind::Normal => 0,
+            };
+            if level == 0 && line.values.is_empty() {
+                continue;
+            }
+
+            let indent = self.config.indent.repeat(level);
+            self.document.push_str(&format!("{}- ", indent));
+            if let LineKind::CodeBlock(code) = &line.kind {
+                // The rest of the block lines up with the text after "- ".
+                let text = line.code().unwrap_or_default();
+                let fence = fence(text);
+                self.document
+                    .push_str(&format!("{}{}\n", fence, code.language()));
+                for l in text.lines() {
+                    self.document.push_str(&format!("{}  {}\n", indent, l));
+                }
+                self.document.push_str(&format!("{}  {}\n", indent, fence));
+                continue;
+            }
+            self.visit_line(line);
+            self.document.push('\n');
+
+            if let LineKind::List(list) = &line.kind {
+                if list.kind == ListKind::Decimal {
+                    self.document
+                        .push_str(&format!("{}  logseq.order-list-type:: number\n", indent));
+                }
+            }
+        }
+    }
+
+    fn visit_hashtag(&mut self, value: &HashTag) {
+        self.document.push_str(&format!("#{}", value.value));
+    }
+
+    fn visit_bracket_internal_link(&mut self, value: &InternalLink) {
+        self.document.push_str(&format!("[[{}]]", value.title));
+    }
+
+    fn visit_bracket_external_link(&mut self, value: &ExternalLink) {
+        if let Some(title) = &value.title {
+            self.document
+                .push_str(&format!("[{}]({})", title, value.url));
+        } else {
+            self.document.push_str(&value.url);
+        }
+    }
+
+    fn visit_bracket_emphasis(&mut self, value: &crate::Emphasis) {
+        let mut tmp = value.text.to_string();
+        if value.bold > 0 {
+            tmp = format!("**{}**", tmp);
+        }
+        if value.italic > 0 {
+            tmp = format!("_{}_", tmp);
+        }
+        if value.strikethrough > 0 {
+            tmp = format!("~~{}~~", tmp);
+        }
+        self.document.push_str(&tmp);
+    }
+
+    fn visit_bracket_heading(&mut self, value: &Heading) {
+        self.document.push_str(&format!(
+            "{} {}",
+            "#".repeat(value.level as usize),
+            value.text
+        ));
+    }
+
+    fn visit_block_quote(&mut self, value: &crate::BlockQuote) {
+        self.document.push_str(&format!("`{}`", value.value));
+    }
+
+    fn visit_text(&mut self, text: &Text) {
+        self.document.push_str(&text.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_page;
+
+    fn generate(text: &str) -> String {
+        LogseqGen::new(LogseqGenConfig::default()).generate(&parse_page(text).unwrap())
+    }
+
+    #[test]
+    fn nested_list_test() {
+        assert_eq!(
+            generate("top\n\tfirst\n\t\tnested\n\t1. one\n\t2. two\n\nafter"),
+            "- top\n\
+             \t- first\n\
+             \t\t- nested\n\
+             \t- one\n\
+             \t  logseq.order-list-type:: number\n\
+             \t- two\n\
+             \t  logseq.order-list-type:: number\n\
+             - after\n"
+        );
+    }
+
+    #[test]
+    fn link_test() {
+        assert_eq!(
+            generate("see [Some Page], #tag and [Rust https://www.rust-lang.org/] at https://x.io"),
+            "- see [[Some Page]], #tag and [Rust](https://www.rust-lang.org/) at https://x.io\n"
+        );
+    }
+
+    #[test]
+    fn code_block_test() {
+        assert_eq!(
+            generate("\tcode:main.rs\n\t fn main() {}\n\t let s = \"```\";"),
+            "\t- ````rs\n\t  fn main() {}\n\t  let s = \"```\";\n\t  ````\n"
+        );
+    }
+
+    #[test]
+    fn page_properties_test() {
+        let mut page = parse_page("body").unwrap();
+        page.meta.title = Some("Title".to_string());
+        page.meta.created = Some(1);
+        page.meta.updated = Some(2);
+        assert_eq!(
+            LogseqGen::new(LogseqGenConfig::default()).generate(&page),
+            "title:: Title\ncreated-at:: 1000\nupdated-at:: 2000\n- body\n"
+        );
+    }
+}
@@ -1,108 +1,528 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    ops::RangeInclusive,
+};
+
 use crate::{
-    ast::{ExternalLink, HashTag, InternalLink, Page, Text},
-    Bracket, BracketKind, Heading, LineKind, ListKind, Syntax, SyntaxKind,
+    ast::{Emphasis, ExternalLink, HashTag, InternalLink, Line, Page, Text},
+    graph::Related,
+    title, Bracket, BracketKind, Heading, LineKind, ListKind, Syntax, SyntaxKind,
 };
 
-use super::{TransformCommand, Visitor};
+use super::{output::Output, LineCommand, Visit, Visitor};
+
+/// What a bold level turns into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BoldMapping {
+    Heading(u8),
+    Bold,
+}
+
+/// Bold levels accepted as the one that becomes H1, so that every promoted
+/// line gets one of the six Markdown heading levels.
+pub const H1_LEVELS: RangeInclusive<u8> = 1..=6;
 
+/// Promotes bold lines to headings.
+///
+/// Only an emphasis that is the sole content of a non-list line is promoted,
+/// according to `levels`; bold levels missing from the table stay bold. With
+/// `auto`, the table is instead derived from the bold levels used on each
+/// page: the biggest becomes H2, the next H3 and so on.
 pub struct MarkdownPass {
-    pub h1_level: u8,
-    pub bold_to_h: bool,
+    pub levels: BTreeMap<u8, BoldMapping>,
+    pub auto: bool,
 }
 
-impl Default for MarkdownPass {
-    fn default() -> Self {
+impl MarkdownPass {
+    /// Table where bold level `h1_level` becomes H1, `h1_level - 1` H2 and so
+    /// on. Single bold is only promoted when `bold_to_h` is set. `h1_level`
+    /// is clamped to `H1_LEVELS`.
+    pub fn from_levels(h1_level: u8, bold_to_h: bool) -> Self {
+        let h1_level = h1_level.clamp(*H1_LEVELS.start(), *H1_LEVELS.end());
+        let levels = (1..=h1_level)
+            .filter(|&bold| bold > 1 || bold_to_h)
+            .map(|bold| {
+                (
+                    bold,
+                    BoldMapping::Heading((h1_level - bold).saturating_add(1)),
+                )
+            })
+            .collect();
         Self {
-            h1_level: 3,
-            bold_to_h: false,
+            levels,
+            auto: false,
         }
     }
+
+    fn auto_levels(page: &Page) -> BTreeMap<u8, BoldMapping> {
+        let bolds: BTreeSet<u8> = page
+            .lines
+            .iter()
+            .filter_map(sole_emphasis)
+            .map(|emphasis| emphasis.bold)
+            .filter(|&bold| bold > 0)
+            .collect();
+        bolds
+            .into_iter()
+            .rev()
+            .zip(2..=6)
+            .map(|(bold, h)| (bold, BoldMapping::Heading(h)))
+            .collect()
+    }
+}
+
+impl Default for MarkdownPass {
+    fn default() -> Self {
+        Self::from_levels(3, false)
+    }
+}
+
+/// The emphasis of a normal line that contains nothing else but whitespace.
+fn sole_emphasis<'l, 'a>(line: &'l Line<'a>) -> Option<&'l Emphasis<'a>> {
+    if line.kind != LineKind::Normal {
+        return None;
+    }
+    let mut values = line.values.iter().filter(|v| match &v.kind {
+        SyntaxKind::Text(text) => !text.value.trim().is_empty(),
+        _ => true,
+    });
+    match (values.next(), values.next()) {
+        (
+            Some(Syntax {
+                kind:
+                    SyntaxKind::Bracket(Bracket {
+                        kind: BracketKind::Emphasis(emphasis),
+                    }),
+            }),
+            None,
+        ) => Some(emphasis),
+        _ => None,
+    }
 }
 
 impl Visitor for MarkdownPass {
-    fn visit_bracket_emphasis(&mut self, emphasis: &crate::Emphasis) -> Option<TransformCommand> {
-        let h_level = (self.h1_level + 1).saturating_sub(emphasis.bold);
-        if (emphasis.bold > 1 || self.bold_to_h) && h_level <= self.h1_level && h_level > 0 {
-            Some(TransformCommand::Replace(Syntax::new(SyntaxKind::Bracket(
-                Bracket::new(BracketKind::Heading(Heading::new(&emphasis.text, h_level))),
-            ))))
-        } else {
-            None
+    fn visit(&mut self, value: &mut Page) {
+        if self.auto {
+            self.levels = Self::auto_levels(value);
         }
+        self.visit_page(value);
+    }
+
+    fn visit_line<'a>(&mut self, value: &mut Line<'a>) -> Option<LineCommand<'a>> {
+        let emphasis = sole_emphasis(value)?;
+        if let Some(BoldMapping::Heading(h_level)) = self.levels.get(&emphasis.bold) {
+            let heading = Heading::new(emphasis.text.clone(), *h_level);
+            value.values = vec![Syntax::new(SyntaxKind::Bracket(Bracket::new(
+                BracketKind::Heading(heading),
+            )))];
+        }
+        None
     }
 }
 
+#[non_exhaustive]
 pub struct MarkdownGenConfig {
-    indent: String,
+    pub indent: String,
+    pub front_matter: Option<FrontMatter>,
+    pub linked_from: Option<LinkedFrom>,
+    pub link_style: LinkStyle,
+    /// Base URL of each project that `[/project/page]` links point into.
+    pub projects: BTreeMap<String, String>,
 }
 
 impl Default for MarkdownGenConfig {
     fn default() -> Self {
         Self {
             indent: "   ".to_string(),
+            front_matter: None,
+            linked_from: None,
+            link_style: LinkStyle::default(),
+            projects: BTreeMap::new(),
+        }
+    }
+}
+
+/// How links to other pages of the project are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum LinkStyle {
+    /// `[title](title.md)`
+    #[default]
+    Markdown,
+    /// `[[title]]`, as used by Obsidian and Foam.
+    Wiki,
+}
+
+/// Section appended to the document listing the pages related to it.
+#[derive(Debug, Clone)]
+pub struct LinkedFrom {
+    pub heading: String,
+    /// Also list pages that share a link with this one.
+    pub two_hop: bool,
+}
+
+impl Default for LinkedFrom {
+    fn default() -> Self {
+        Self {
+            heading: "Linked from".to_string(),
+            two_hop: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FrontMatterFormat {
+    /// `---` delimited, as used by Hugo and Jekyll.
+    Yaml,
+    /// `+++` delimited, as used by Zola and Hugo.
+    Toml,
+}
+
+/// Front matter written before the document for static site generators.
+///
+/// `title`, `date` and `lastmod` come from the page metadata and `tags` from
+/// every hashtag on the page; `aliases` and `fields` are written as given.
+#[derive(Debug, Clone)]
+pub struct FrontMatter {
+    pub format: FrontMatterFormat,
+    pub aliases: Vec<String>,
+    pub fields: Vec<(String, FieldValue)>,
+}
+
+/// Value of a static front matter field. Booleans and numbers are written
+/// unquoted, so that site generators read them as such.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Bool(value)
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Integer(value)
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::String(value.to_string())
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::String(value)
+    }
+}
+
+impl FieldValue {
+    fn render(&self, format: FrontMatterFormat) -> String {
+        match self {
+            FieldValue::Bool(b) => b.to_string(),
+            FieldValue::Integer(n) => n.to_string(),
+            // `{:?}` keeps a decimal point or exponent, so the value reads
+            // back as a float.
+            FieldValue::Float(x) if x.is_finite() => format!("{:?}", x),
+            FieldValue::Float(x) => {
+                let name = if x.is_nan() { "nan" } else { "inf" };
+                let sign = if x.is_sign_negative() && !x.is_nan() {
+                    "-"
+                } else {
+                    ""
+                };
+                match format {
+                    FrontMatterFormat::Yaml => format!("{}.{}", sign, name),
+                    FrontMatterFormat::Toml => format!("{}{}", sign, name),
+                }
+            }
+            FieldValue::String(s) => quote(s),
+        }
+    }
+}
+
+impl FrontMatter {
+    pub fn new(format: FrontMatterFormat) -> Self {
+        Self {
+            format,
+            aliases: vec![],
+            fields: vec![],
+        }
+    }
+
+    fn render(&self, page: &Page) -> String {
+        let mut tags = TagCollector::default();
+        tags.visit(page);
+
+        let (delimiter, separator) = match self.format {
+            FrontMatterFormat::Yaml => ("---", ":"),
+            FrontMatterFormat::Toml => ("+++", " ="),
+        };
+        let list = |values: &[String]| {
+            let values: Vec<String> = values.iter().map(|v| quote(v)).collect();
+            format!("[{}]", values.join(", "))
+        };
+
+        let mut out = format!("{}\n", delimiter);
+        let mut field = |key: &str, value: String| {
+            out.push_str(&format!("{}{} {}\n", key, separator, value));
+        };
+        if let Some(title) = &page.meta.title {
+            field("title", quote(title));
+        }
+        if !tags.tags.is_empty() {
+            field("tags", list(&tags.tags));
+        }
+        if !self.aliases.is_empty() {
+            field("aliases", list(&self.aliases));
+        }
+        if let Some(created) = page.meta.created {
+            field("date", rfc3339(created));
+        }
+        if let Some(updated) = page.meta.updated {
+            field("lastmod", rfc3339(updated));
+        }
+        for (key, value) in &self.fields {
+            field(&quote_key(key), value.render(self.format));
         }
+        out.push_str(&format!("{}\n", delimiter));
+        out
     }
 }
 
-pub struct MarkdownGen {
-    document: String,
+/// Double-quoted string, valid in both YAML and TOML.
+fn quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `key` as is when it is a bare key in both YAML and TOML, quoted otherwise.
+fn quote_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+/// A code fence longer than any run of backticks in `code`, so the code can
+/// not close it.
+pub(crate) fn fence(code: &str) -> String {
+    let longest = code
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Formats unix seconds as an RFC 3339 UTC timestamp.
+fn rfc3339(secs: i64) -> String {
+    let days = secs.div_euclid(86_400);
+    let time = secs.rem_euclid(86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time % 3_600 / 60,
+        time % 60
+    )
+}
+
+#[derive(Default)]
+struct TagCollector {
+    tags: Vec<String>,
+}
+
+impl Visit for TagCollector {
+    fn visit_hashtag(&mut self, value: &HashTag) {
+        if !self.tags.iter().any(|tag| *tag == value.value) {
+            self.tags.push(value.value.to_string());
+        }
+    }
+}
+
+/// Writes pages as Markdown to `W`, or to a `String` with `generate`.
+pub struct MarkdownGen<W = Vec<u8>> {
+    document: Output<W>,
     config: MarkdownGenConfig,
+    related: Related,
 }
 
 impl MarkdownGen {
     pub fn new(config: MarkdownGenConfig) -> Self {
+        Self::with_writer(config, Vec::new())
+    }
+
+    pub fn generate(&mut self, page: &Page) -> String {
+        self.write(page).expect("writing to a Vec can not fail");
+        self.document.take_string()
+    }
+}
+
+impl<W: io::Write> MarkdownGen<W> {
+    pub fn with_writer(config: MarkdownGenConfig, writer: W) -> Self {
         Self {
-            document: String::new(),
+            document: Output::new(writer),
             config,
+            related: Related::default(),
         }
     }
 
-    pub fn generate(&mut self, page: &mut Page) -> String {
+    /// Pages listed in the `linked_from` section.
+    pub fn with_related(mut self, related: Related) -> Self {
+        self.related = related;
+        self
+    }
+
+    /// Link to the page `title`, or into another project for `/project/page`.
+    fn link(&self, text: &str, title: &str) -> String {
+        if let Some(project_title) = title.strip_prefix('/') {
+            // Pages of other projects are only reachable through their URL;
+            // without one the link stays as text.
+            let (project, page) = project_title.split_once('/').unwrap_or((project_title, ""));
+            return match self.config.projects.get(project) {
+                Some(url) => {
+                    let url = url.trim_end_matches('/');
+                    format!("[{}]({}/{})", text, url, title::url_segment(page))
+                }
+                None => text.to_string(),
+            };
+        }
+        match self.config.link_style {
+            LinkStyle::Markdown => format!("[{}]({}.md)", text, title::link_path(title)),
+            LinkStyle::Wiki if text == title => format!("[[{}]]", title),
+            LinkStyle::Wiki => format!("[[{}|{}]]", title, text),
+        }
+    }
+
+    pub fn write(&mut self, page: &Page) -> io::Result<()> {
+        if let Some(front_matter) = &self.config.front_matter {
+            self.document.push_str(&front_matter.render(page));
+        }
         self.visit(page);
-        self.document.clone()
+        if let Some(linked_from) = &self.config.linked_from {
+            let mut titles = self.related.backlinks.clone();
+            if linked_from.two_hop {
+                titles.extend(self.related.two_hop.iter().cloned());
+            }
+            if !titles.is_empty() {
+                self.document
+                    .push_str(&format!("\n## {}\n", linked_from.heading));
+                for title in titles {
+                    let link = self.link(&title, &title);
+                    self.document.push_str(&format!("* {}\n", link));
+                }
+            }
+        }
+        self.document.finish()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.document.into_inner()
     }
 }
 
-impl Visitor for MarkdownGen {
-    fn visit_page(&mut self, value: &mut Page) {
-        for line in value.lines.iter_mut() {
-            if let LineKind::List(list) = &line.kind {
-                let indent = self.config.indent.repeat(list.level - 1);
-                match &list.kind {
-                    ListKind::Disc => self.document.push_str(&format!("{}* ", indent)),
-                    ListKind::Decimal => self.document.push_str(&format!("{}1. ", indent)),
-                    _ => {}
+impl<W: io::Write> Visit for MarkdownGen<W> {
+    fn visit_page(&mut self, value: &Page) {
+        for line in value.lines.iter() {
+            match &line.kind {
+                LineKind::List(list) => {
+                    let indent = self.config.indent.repeat(list.level.saturating_sub(1));
+                    match &list.kind {
+                        ListKind::Disc => self.document.push_str(&format!("{}* ", indent)),
+                        ListKind::Decimal => self.document.push_str(&format!("{}1. ", indent)),
+                        _ => {}
+                    }
                 }
+                LineKind::CodeBlock(code) => {
+                    let indent = self.config.indent.repeat(code.level);
+                    let text = line.code().unwrap_or_default();
+                    let fence = fence(text);
+                    self.document
+                        .push_str(&format!("{}{}{}\n", indent, fence, code.language()));
+                    for l in text.lines() {
+                        self.document.push_str(&format!("{}{}\n", indent, l));
+                    }
+                    self.document.push_str(&format!("{}{}\n", indent, fence));
+                    continue;
+                }
+                LineKind::Normal => {}
             }
             self.visit_line(line);
             self.document.push('\n');
         }
     }
 
-    fn visit_hashtag(&mut self, value: &HashTag) -> Option<TransformCommand> {
-        self.document
-            .push_str(&format!("[#{t}](#{t}.md)", t = value.value));
-        None
+    fn visit_hashtag(&mut self, value: &HashTag) {
+        let link = self.link(&format!("#{}", value.value), &value.value);
+        self.document.push_str(&link);
     }
 
-    fn visit_bracket_internal_link(&mut self, value: &InternalLink) -> Option<TransformCommand> {
-        self.document
-            .push_str(&format!("[{t}]({t}).md", t = value.title));
-        None
+    fn visit_bracket_internal_link(&mut self, value: &InternalLink) {
+        let link = self.link(&value.title, &value.title);
+        self.document.push_str(&link);
     }
 
-    fn visit_bracket_external_link(&mut self, value: &ExternalLink) -> Option<TransformCommand> {
+    fn visit_bracket_external_link(&mut self, value: &ExternalLink) {
         if let Some(title) = &value.title {
             self.document
                 .push_str(&format!("[{}]({})", title, value.url));
         } else {
-            self.document.push_str(&value.url.to_string());
+            self.document.push_str(&value.url);
         }
-        None
     }
 
-    fn visit_bracket_emphasis(&mut self, value: &crate::Emphasis) -> Option<TransformCommand> {
-        let mut tmp = value.text.clone();
+    fn visit_bracket_emphasis(&mut self, value: &crate::Emphasis) {
+        let mut tmp = value.text.to_string();
         if value.bold > 0 {
             tmp = format!("**{}**", tmp);
         }
@@ -113,25 +533,208 @@ impl Visitor for MarkdownGen {
             tmp = format!("~~{}~~", tmp);
         }
         self.document.push_str(&tmp);
-        None
     }
 
-    fn visit_bracket_heading(&mut self, value: &Heading) -> Option<TransformCommand> {
+    fn visit_bracket_heading(&mut self, value: &Heading) {
         self.document.push_str(&format!(
             "{} {}",
             "#".repeat(value.level as usize),
             value.text
         ));
-        None
     }
 
-    fn visit_block_quote(&mut self, value: &crate::BlockQuote) -> Option<TransformCommand> {
-        self.document.push_str(&value.value.to_string());
-        None
+    fn visit_block_quote(&mut self, value: &crate::BlockQuote) {
+        self.document.push_str(&value.value);
     }
 
-    fn visit_text(&mut self, text: &Text) -> Option<TransformCommand> {
-        self.document.push_str(&text.value.to_string());
-        None
+    fn visit_text(&mut self, text: &Text) {
+        self.document.push_str(&text.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(values: Vec<Syntax>) -> Line {
+        Line::new(LineKind::Normal, values)
+    }
+
+    fn bold(text: &str, level: u8) -> Syntax<'_> {
+        Syntax::new(SyntaxKind::Bracket(Bracket::new(BracketKind::Emphasis(
+            Emphasis::bold_level(text, level),
+        ))))
+    }
+
+    fn heading(text: &str, level: u8) -> Syntax<'_> {
+        Syntax::new(SyntaxKind::Bracket(Bracket::new(BracketKind::Heading(
+            Heading::new(text, level),
+        ))))
+    }
+
+    #[test]
+    fn markdown_pass_test() {
+        let text = Syntax::new(SyntaxKind::Text(Text::new("see ")));
+        let mut page = Page {
+            lines: vec![
+                line(vec![bold("a", 3)]),
+                line(vec![
+                    bold("b", 1),
+                    Syntax::new(SyntaxKind::Text(Text::new(" "))),
+                ]),
+                line(vec![text.clone(), bold("c", 2)]),
+            ],
+            ..Default::default()
+        };
+        MarkdownPass::from_levels(3, false).visit(&mut page);
+        assert_eq!(page.lines[0].values, vec![heading("a", 1)]);
+        assert_eq!(page.lines[1].values[0], bold("b", 1));
+        assert_eq!(page.lines[2].values, vec![text, bold("c", 2)]);
+
+        let pass = MarkdownPass::from_levels(255, true);
+        assert_eq!(pass.levels.len(), 6);
+        assert_eq!(pass.levels[&6], BoldMapping::Heading(1));
+        assert_eq!(pass.levels[&1], BoldMapping::Heading(6));
+    }
+
+    #[test]
+    fn markdown_pass_auto_test() {
+        let mut page = Page {
+            lines: vec![
+                line(vec![bold("a", 5)]),
+                line(vec![bold("b", 1)]),
+                line(vec![bold("c", 5)]),
+            ],
+            ..Default::default()
+        };
+        let mut pass = MarkdownPass {
+            auto: true,
+            ..Default::default()
+        };
+        pass.visit(&mut page);
+        assert_eq!(page.lines[0].values, vec![heading("a", 2)]);
+        assert_eq!(page.lines[1].values, vec![heading("b", 3)]);
+        assert_eq!(page.lines[2].values, vec![heading("c", 2)]);
+    }
+
+    #[test]
+    fn link_test() {
+        let page = crate::parser::page("[Foo Bar] #tag [/help/Some Page?] [/other/x]")
+            .unwrap()
+            .1;
+        let generate = |link_style| {
+            MarkdownGen::new(MarkdownGenConfig {
+                link_style,
+                projects: BTreeMap::from([("help".to_string(), "https://x.io/help/".to_string())]),
+                ..Default::default()
+            })
+            .generate(&page)
+        };
+        assert_eq!(
+            generate(LinkStyle::Markdown),
+            "[Foo Bar](foo_bar.md) [#tag](tag.md) [/help/Some Page?](https://x.io/help/Some_Page%3F) /other/x\n"
+        );
+        assert_eq!(
+            generate(LinkStyle::Wiki),
+            "[[Foo Bar]] [[tag|#tag]] [/help/Some Page?](https://x.io/help/Some_Page%3F) /other/x\n"
+        );
+    }
+
+    #[test]
+    fn code_block_test() {
+        let page = crate::parse_page(
+            "code:main.rs\n fn main() {}\nlist\n\tcode:doc.md\n\t ```sh\n\t ls\n\t ```",
+        )
+        .unwrap();
+        assert_eq!(
+            MarkdownGen::new(MarkdownGenConfig::default()).generate(&page),
+            "```rs\nfn main() {}\n```\nlist\n   ````md\n   ```sh\n   ls\n   ```\n   ````\n"
+        );
+        assert_eq!(fence(""), "```");
+        assert_eq!(fence("a `b` ````"), "`````");
+    }
+
+    fn front_matter_page() -> Page<'static> {
+        let mut page = crate::parse_page("#rust and #tag\n#rust again")
+            .unwrap()
+            .into_owned();
+        page.meta.title = Some("Say \"hi\"".to_string());
+        page.meta.created = Some(0);
+        page.meta.updated = Some(951_782_400);
+        page
+    }
+
+    fn front_matter(format: FrontMatterFormat) -> FrontMatter {
+        FrontMatter {
+            format,
+            aliases: vec!["old\\name".to_string()],
+            fields: vec![
+                ("draft".to_string(), true.into()),
+                ("weight".to_string(), 10.into()),
+                ("ratio".to_string(), 1.0.into()),
+                ("limit".to_string(), f64::NEG_INFINITY.into()),
+                ("my key: x".to_string(), "line\nbreak".into()),
+            ],
+        }
+    }
+
+    #[test]
+    fn yaml_front_matter_test() {
+        assert_eq!(
+            front_matter(FrontMatterFormat::Yaml).render(&front_matter_page()),
+            "---\n\
+             title: \"Say \\\"hi\\\"\"\n\
+             tags: [\"rust\", \"tag\"]\n\
+             aliases: [\"old\\\\name\"]\n\
+             date: 1970-01-01T00:00:00Z\n\
+             lastmod: 2000-02-29T00:00:00Z\n\
+             draft: true\n\
+             weight: 10\n\
+             ratio: 1.0\n\
+             limit: -.inf\n\
+             \"my key: x\": \"line\\nbreak\"\n\
+             ---\n"
+        );
+    }
+
+    #[test]
+    fn toml_front_matter_test() {
+        assert_eq!(
+            front_matter(FrontMatterFormat::Toml).render(&front_matter_page()),
+            "+++\n\
+             title = \"Say \\\"hi\\\"\"\n\
+             tags = [\"rust\", \"tag\"]\n\
+             aliases = [\"old\\\\name\"]\n\
+             date = 1970-01-01T00:00:00Z\n\
+             lastmod = 2000-02-29T00:00:00Z\n\
+             draft = true\n\
+             weight = 10\n\
+             ratio = 1.0\n\
+             limit = -inf\n\
+             \"my key: x\" = \"line\\nbreak\"\n\
+             +++\n"
+        );
+        #[cfg(feature = "config")]
+        {
+            let rendered = front_matter(FrontMatterFormat::Toml).render(&front_matter_page());
+            let table: toml::Table = toml::from_str(rendered.trim_matches(['+', '\n'])).unwrap();
+            assert_eq!(table["my key: x"].as_str(), Some("line\nbreak"));
+            assert_eq!(table["draft"].as_bool(), Some(true));
+            assert_eq!(table["weight"].as_integer(), Some(10));
+            assert_eq!(table["ratio"].as_float(), Some(1.0));
+            assert_eq!(table["limit"].as_float(), Some(f64::NEG_INFINITY));
+        }
+        // Without a title or tags only the delimiters remain.
+        assert_eq!(
+            FrontMatter::new(FrontMatterFormat::Toml).render(&Page::default()),
+            "+++\n+++\n"
+        );
+    }
+
+    #[test]
+    fn rfc3339_test() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_682_061_601), "2023-04-21T07:20:01Z");
     }
 }
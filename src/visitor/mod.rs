@@ -1,59 +1,133 @@
-use std::collections::HashMap;
-
 use crate::{
     ast::{
-        Bracket, BracketKind, Emphasis, ExternalLink, HashTag, Heading, InternalLink, Line, Page,
-        Syntax, SyntaxKind, Text,
+        Bracket, BracketKind, Emphasis, ExternalLink, HashTag, Heading, InternalLink, Line,
+        LineKind, Page, Syntax, SyntaxKind, Text,
     },
     BlockQuote,
 };
 
+mod fold;
+pub mod link_rewrite;
+pub mod logseq;
 pub mod markdown;
+pub mod org;
+mod output;
+pub mod plain_text;
+pub mod scrapbox;
+pub mod strip_tags;
+mod visit;
+
+pub use fold::Fold;
+pub use visit::Visit;
+
+/// What to do with a syntax node after visiting it.
+///
+/// Nodes produced by a command are not visited again.
+#[derive(Debug)]
+pub enum TransformCommand<'a> {
+    Replace(Syntax<'a>),
+    /// Replace the node with any number of nodes.
+    ReplaceWith(Vec<Syntax<'a>>),
+    /// Keep the node and insert nodes in front of it.
+    InsertBefore(Vec<Syntax<'a>>),
+    /// Keep the node and insert nodes behind it.
+    InsertAfter(Vec<Syntax<'a>>),
+    Delete,
+}
 
+/// What to do with a line after visiting it, applied by `Visitor::visit_page`.
+///
+/// Lines produced by a command are not visited again.
 #[derive(Debug)]
-pub enum TransformCommand {
-    Replace(Syntax),
-    #[allow(dead_code)]
+pub enum LineCommand<'a> {
+    Replace(Line<'a>),
+    /// Replace the line with any number of lines.
+    ReplaceWith(Vec<Line<'a>>),
+    /// Keep the line and insert lines above it.
+    InsertBefore(Vec<Line<'a>>),
+    /// Keep the line and insert lines below it.
+    InsertAfter(Vec<Line<'a>>),
+    /// Keep the values but change the kind of the line: normal, list item or
+    /// code block. A code block expects its code as a single `Text`.
+    SetKind(LineKind),
     Delete,
 }
 
 pub trait Visitor {
-    fn visit(&mut self, value: &mut Page) {
+    fn visit(&mut self, value: &mut Page<'_>) {
         self.visit_page(value);
     }
 
-    fn visit_page(&mut self, value: &mut Page) {
-        for line in value.lines.iter_mut() {
-            self.visit_line(line);
+    fn visit_page<'a>(&mut self, value: &mut Page<'a>) {
+        let commands: Vec<Option<LineCommand<'a>>> = value
+            .lines
+            .iter_mut()
+            .map(|line| self.visit_line(line))
+            .collect();
+        if commands.iter().all(Option::is_none) {
+            return;
         }
-    }
 
-    fn visit_line(&mut self, value: &mut Line) {
-        let mut commands = HashMap::new();
-        for (i, item) in value.values.iter().enumerate() {
-            let command = self.visit_syntax(item);
-            if let Some(c) = command {
-                commands.insert(i, c);
+        let lines = std::mem::take(&mut value.lines);
+        for (mut line, command) in lines.into_iter().zip(commands) {
+            match command {
+                None => value.lines.push(line),
+                Some(LineCommand::Replace(l)) => value.lines.push(l),
+                Some(LineCommand::ReplaceWith(l)) => value.lines.extend(l),
+                Some(LineCommand::InsertBefore(l)) => {
+                    value.lines.extend(l);
+                    value.lines.push(line);
+                }
+                Some(LineCommand::InsertAfter(l)) => {
+                    value.lines.push(line);
+                    value.lines.extend(l);
+                }
+                Some(LineCommand::SetKind(kind)) => {
+                    line.kind = kind;
+                    value.lines.push(line);
+                }
+                Some(LineCommand::Delete) => {}
             }
         }
+    }
 
-        // Replace
-        for (&i, command) in &commands {
-            if let TransformCommand::Replace(s) = command {
-                value.values[i] = s.clone();
-            }
+    /// Line-level hook. Overriding passes should call `visit_values` to keep
+    /// visiting the syntax nodes of the line.
+    fn visit_line<'a>(&mut self, value: &mut Line<'a>) -> Option<LineCommand<'a>> {
+        self.visit_values(value);
+        None
+    }
+
+    fn visit_values<'a>(&mut self, value: &mut Line<'a>) {
+        let commands: Vec<Option<TransformCommand<'a>>> = value
+            .values
+            .iter()
+            .map(|item| self.visit_syntax(item))
+            .collect();
+        if commands.iter().all(Option::is_none) {
+            return;
         }
 
-        // Delete
-        let mut i = 0;
-        value.values.retain(|_| {
-            let retain = !matches!(commands.get(&i), Some(TransformCommand::Delete));
-            i += 1;
-            retain
-        });
+        let values = std::mem::take(&mut value.values);
+        for (item, command) in values.into_iter().zip(commands) {
+            match command {
+                None => value.values.push(item),
+                Some(TransformCommand::Replace(s)) => value.values.push(s),
+                Some(TransformCommand::ReplaceWith(s)) => value.values.extend(s),
+                Some(TransformCommand::InsertBefore(s)) => {
+                    value.values.extend(s);
+                    value.values.push(item);
+                }
+                Some(TransformCommand::InsertAfter(s)) => {
+                    value.values.push(item);
+                    value.values.extend(s);
+                }
+                Some(TransformCommand::Delete) => {}
+            }
+        }
     }
 
-    fn visit_syntax(&mut self, value: &Syntax) -> Option<TransformCommand> {
+    fn visit_syntax<'a>(&mut self, value: &Syntax<'a>) -> Option<TransformCommand<'a>> {
         match &value.kind {
             SyntaxKind::HashTag(v) => self.visit_hashtag(v),
             SyntaxKind::Bracket(v) => self.visit_bracket(v),
@@ -62,11 +136,11 @@ pub trait Visitor {
         }
     }
 
-    fn visit_hashtag(&mut self, _value: &HashTag) -> Option<TransformCommand> {
+    fn visit_hashtag<'a>(&mut self, _value: &HashTag<'a>) -> Option<TransformCommand<'a>> {
         None
     }
 
-    fn visit_bracket(&mut self, value: &Bracket) -> Option<TransformCommand> {
+    fn visit_bracket<'a>(&mut self, value: &Bracket<'a>) -> Option<TransformCommand<'a>> {
         match &value.kind {
             BracketKind::InternalLink(v) => self.visit_bracket_internal_link(v),
             BracketKind::ExternalLink(v) => self.visit_bracket_external_link(v),
@@ -75,27 +149,204 @@ pub trait Visitor {
         }
     }
 
-    fn visit_bracket_internal_link(&mut self, _value: &InternalLink) -> Option<TransformCommand> {
+    fn visit_bracket_internal_link<'a>(
+        &mut self,
+        _value: &InternalLink<'a>,
+    ) -> Option<TransformCommand<'a>> {
         None
     }
 
-    fn visit_bracket_external_link(&mut self, _value: &ExternalLink) -> Option<TransformCommand> {
+    fn visit_bracket_external_link<'a>(
+        &mut self,
+        _value: &ExternalLink<'a>,
+    ) -> Option<TransformCommand<'a>> {
         None
     }
 
-    fn visit_bracket_emphasis(&mut self, _value: &Emphasis) -> Option<TransformCommand> {
+    fn visit_bracket_emphasis<'a>(
+        &mut self,
+        _value: &Emphasis<'a>,
+    ) -> Option<TransformCommand<'a>> {
         None
     }
 
-    fn visit_bracket_heading(&mut self, _value: &Heading) -> Option<TransformCommand> {
+    fn visit_bracket_heading<'a>(&mut self, _value: &Heading<'a>) -> Option<TransformCommand<'a>> {
         None
     }
 
-    fn visit_block_quote(&mut self, _value: &BlockQuote) -> Option<TransformCommand> {
+    fn visit_block_quote<'a>(&mut self, _value: &BlockQuote<'a>) -> Option<TransformCommand<'a>> {
         None
     }
 
-    fn visit_text(&mut self, _value: &Text) -> Option<TransformCommand> {
+    fn visit_text<'a>(&mut self, _value: &Text<'a>) -> Option<TransformCommand<'a>> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::List;
+
+    fn text(value: &str) -> Syntax<'_> {
+        Syntax::new(SyntaxKind::Text(Text::new(value)))
+    }
+
+    struct Expand;
+
+    impl Visitor for Expand {
+        fn visit_text<'a>(&mut self, value: &Text<'a>) -> Option<TransformCommand<'a>> {
+            match value.value.as_ref() {
+                "a" => Some(TransformCommand::InsertBefore(vec![text("<"), text("<")])),
+                "b" => Some(TransformCommand::InsertAfter(vec![text(">")])),
+                "c" => Some(TransformCommand::ReplaceWith(vec![text("c1"), text("c2")])),
+                "d" => Some(TransformCommand::Delete),
+                "e" => Some(TransformCommand::Replace(text("E"))),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn transform_commands_test() {
+        let mut page = Page {
+            lines: vec![Line::new(
+                LineKind::Normal,
+                ["a", "b", "x", "c", "d", "e"].map(text).to_vec(),
+            )],
+            ..Default::default()
+        };
+        Expand.visit(&mut page);
+        assert_eq!(
+            page.lines[0].values,
+            ["<", "<", "a", "b", ">", "x", "c1", "c2", "E"]
+                .map(text)
+                .to_vec()
+        );
+    }
+
+    /// Splits lines at `<br>`, drops empty lines and adds a list item below
+    /// lines that are only `:`.
+    struct Restructure;
+
+    impl Visitor for Restructure {
+        fn visit_line<'a>(&mut self, value: &mut Line<'a>) -> Option<LineCommand<'a>> {
+            if value.values.is_empty() {
+                return Some(LineCommand::Delete);
+            }
+            if value.values.contains(&text("<br>")) {
+                let lines = value
+                    .values
+                    .split(|v| *v == text("<br>"))
+                    .map(|values| Line::new(value.kind.clone(), values.to_vec()))
+                    .collect();
+                return Some(LineCommand::ReplaceWith(lines));
+            }
+            if value.values == [text(":")] {
+                return Some(LineCommand::InsertAfter(vec![Line::new(
+                    LineKind::List(List::disc(1)),
+                    vec![text("item")],
+                )]));
+            }
+            None
+        }
+    }
+
+    #[test]
+    fn line_commands_test() {
+        let mut page = Page {
+            lines: vec![
+                Line::new(LineKind::Normal, vec![text("a"), text("<br>"), text("b")]),
+                Line::new(LineKind::Normal, vec![]),
+                Line::new(LineKind::Normal, vec![text(":")]),
+            ],
+            ..Default::default()
+        };
+        Restructure.visit(&mut page);
+        assert_eq!(
+            page.lines,
+            vec![
+                Line::new(LineKind::Normal, vec![text("a")]),
+                Line::new(LineKind::Normal, vec![text("b")]),
+                Line::new(LineKind::Normal, vec![text(":")]),
+                Line::new(LineKind::List(List::disc(1)), vec![text("item")]),
+            ]
+        );
+    }
+
+    /// Turns lines starting with `-` into list items, puts an `Items:` line
+    /// above lines starting with `*` and replaces lines that are only `x`.
+    struct Lists;
+
+    impl Visitor for Lists {
+        fn visit_line<'a>(&mut self, value: &mut Line<'a>) -> Option<LineCommand<'a>> {
+            match value.values.first() {
+                Some(first) if *first == text("-") => {
+                    value.values.remove(0);
+                    Some(LineCommand::SetKind(LineKind::List(List::disc(1))))
+                }
+                Some(first) if *first == text("*") => {
+                    value.values.remove(0);
+                    Some(LineCommand::InsertBefore(vec![Line::new(
+                        LineKind::Normal,
+                        vec![text("Items:")],
+                    )]))
+                }
+                Some(first) if *first == text("x") => Some(LineCommand::Replace(Line::new(
+                    LineKind::Normal,
+                    vec![text("y")],
+                ))),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn set_kind_insert_before_test() {
+        let mut page = Page {
+            lines: vec![
+                Line::new(LineKind::Normal, vec![text("*"), text("first")]),
+                Line::new(LineKind::Normal, vec![text("-"), text("second")]),
+                Line::new(LineKind::Normal, vec![text("x")]),
+            ],
+            ..Default::default()
+        };
+        Lists.visit(&mut page);
+        assert_eq!(
+            page.lines,
+            vec![
+                Line::new(LineKind::Normal, vec![text("Items:")]),
+                Line::new(LineKind::Normal, vec![text("first")]),
+                Line::new(LineKind::List(List::disc(1)), vec![text("second")]),
+                Line::new(LineKind::Normal, vec![text("y")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn list_level_zero_test() {
+        // A visitor can build a list item without indentation; generators
+        // write it as a top-level item.
+        let page = Page {
+            lines: vec![
+                Line::new(LineKind::List(List::decimal(0)), vec![text("a")]),
+                Line::new(LineKind::List(List::disc(0)), vec![text("b")]),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            markdown::MarkdownGen::new(Default::default()).generate(&page),
+            "1. a\n* b\n"
+        );
+        assert_eq!(
+            org::OrgGen::new(Default::default()).generate(&page),
+            "1. a\n- b\n"
+        );
+        assert_eq!(
+            scrapbox::ScrapboxGen::new(Default::default()).generate(&page),
+            "1. a\nb\n"
+        );
+        plain_text::PlainTextGen::new(Default::default()).generate(&page);
+        logseq::LogseqGen::new(Default::default()).generate(&page);
+    }
+}
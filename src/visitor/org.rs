@@ -0,0 +1,215 @@
+use std::io;
+
+use crate::{
+    ast::{ExternalLink, HashTag, InternalLink, Line, Page, Text},
+    title, Heading, LineKind, ListKind,
+};
+
+use super::{output::Output, Visit};
+
+#[non_exhaustive]
+pub struct OrgGenConfig {
+    pub indent: String,
+}
+
+impl Default for OrgGenConfig {
+    fn default() -> Self {
+        Self {
+            indent: "  ".to_string(),
+        }
+    }
+}
+
+pub struct OrgGen<W = Vec<u8>> {
+    document: Output<W>,
+    config: OrgGenConfig,
+    /// Whether the node being visited starts a normal line, the only place
+    /// an Org headline can be.
+    line_start: bool,
+}
+
+impl OrgGen {
+    pub fn new(config: OrgGenConfig) -> Self {
+        Self::with_writer(config, Vec::new())
+    }
+
+    pub fn generate(&mut self, page: &Page) -> String {
+        self.write(page).expect("writing to a Vec can not fail");
+        self.document.take_string()
+    }
+}
+
+impl<W: io::Write> OrgGen<W> {
+    pub fn with_writer(config: OrgGenConfig, writer: W) -> Self {
+        Self {
+            document: Output::new(writer),
+            config,
+            line_start: false,
+        }
+    }
+
+    pub fn write(&mut self, page: &Page) -> io::Result<()> {
+        self.visit(page);
+        self.document.finish()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.document.into_inner()
+    }
+}
+
+impl<W: io::Write> Visit for OrgGen<W> {
+    fn visit_page(&mut self, value: &Page) {
+        if let Some(title) = &value.meta.title {
+            self.document.push_str(&format!("#+TITLE: {}\n", title));
+        }
+        for line in value.lines.iter() {
+            match &line.kind {
+                LineKind::List(list) => {
+                    let indent = self.config.indent.repeat(list.level.saturating_sub(1));
+                    match &list.kind {
+                        ListKind::Disc => self.document.push_str(&format!("{}- ", indent)),
+                        ListKind::Decimal => self.document.push_str(&format!("{}1. ", indent)),
+                        _ => {}
+                    }
+                }
+                LineKind::CodeBlock(code) => {
+                    let indent = self.config.indent.repeat(code.level);
+                    self.document
+                        .push_str(&format!("{}#+BEGIN_SRC {}\n", indent, code.language()));
+                    for l in line.code().unwrap_or_default().lines() {
+                        // Org reads `*` and `#+` at the start of a line as markup
+                        // even in a block; a comma right before them escapes them.
+                        let comma = if l.starts_with('*') || l.starts_with("#+") {
+                            ","
+                        } else {
+                            ""
+                        };
+                        self.document
+                            .push_str(&format!("{}{}{}\n", indent, comma, l));
+                    }
+                    self.document.push_str(&format!("{}#+END_SRC\n", indent));
+                    continue;
+                }
+                LineKind::Normal => {}
+            }
+            self.visit_line(line);
+            self.document.push('\n');
+        }
+    }
+
+    fn visit_line(&mut self, value: &Line) {
+        for (i, item) in value.values.iter().enumerate() {
+            self.line_start = i == 0 && value.kind == LineKind::Normal;
+            self.visit_syntax(item);
+        }
+        self.line_start = false;
+    }
+
+    fn visit_hashtag(&mut self, value: &HashTag) {
+        self.document.push_str(&format!(
+            "[[file:{}.org][#{}]]",
+            title::file_name(&value.value),
+            value.value
+        ));
+    }
+
+    fn visit_bracket_internal_link(&mut self, value: &InternalLink) {
+        self.document.push_str(&format!(
+            "[[file:{}.org][{}]]",
+            title::file_name(&value.title),
+            value.title
+        ));
+    }
+
+    fn visit_bracket_external_link(&mut self, value: &ExternalLink) {
+        if let Some(title) = &value.title {
+            self.document
+                .push_str(&format!("[[{}][{}]]", value.url, title));
+        } else {
+            self.document.push_str(&format!("[[{}]]", value.url));
+        }
+    }
+
+    fn visit_bracket_emphasis(&mut self, value: &crate::Emphasis) {
+        let mut tmp = value.text.to_string();
+        if value.bold > 0 {
+            tmp = format!("*{}*", tmp);
+        }
+        if value.italic > 0 {
+            tmp = format!("/{}/", tmp);
+        }
+        if value.strikethrough > 0 {
+            tmp = format!("+{}+", tmp);
+        }
+        self.document.push_str(&tmp);
+    }
+
+    fn visit_bracket_heading(&mut self, value: &Heading) {
+        if self.line_start {
+            self.document.push_str(&format!(
+                "{} {}",
+                "*".repeat(value.level as usize),
+                value.text
+            ));
+        } else {
+            self.document.push_str(&format!("*{}*", value.text));
+        }
+    }
+
+    fn visit_block_quote(&mut self, value: &crate::BlockQuote) {
+        self.document.push_str(&format!("~{}~", value.value));
+    }
+
+    fn visit_text(&mut self, text: &Text) {
+        self.document.push_str(&text.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bracket, BracketKind, List, Syntax, SyntaxKind};
+
+    fn heading(text: &str, level: u8) -> Syntax<'_> {
+        Syntax::new(SyntaxKind::Bracket(Bracket::new(BracketKind::Heading(
+            Heading::new(text, level),
+        ))))
+    }
+
+    fn text(value: &str) -> Syntax<'_> {
+        Syntax::new(SyntaxKind::Text(Text::new(value)))
+    }
+
+    #[test]
+    fn heading_test() {
+        let page = Page {
+            lines: vec![
+                Line::new(LineKind::Normal, vec![heading("Title", 1)]),
+                Line::new(LineKind::Normal, vec![text("see "), heading("this", 2)]),
+                Line::new(LineKind::List(List::disc(1)), vec![heading("item", 2)]),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            OrgGen::new(OrgGenConfig::default()).generate(&page),
+            "* Title\nsee *this*\n- *item*\n"
+        );
+    }
+
+    #[test]
+    fn code_block_test() {
+        let page = crate::parse_page("code:notes.org\n * item\n #+END_SRC\n plain\n   * x\n a * b")
+            .unwrap();
+        assert_eq!(
+            OrgGen::new(OrgGenConfig::default()).generate(&page),
+            "#+BEGIN_SRC org\n,* item\n,#+END_SRC\nplain\n  * x\na * b\n#+END_SRC\n"
+        );
+        // In a list the block is indented, and the comma goes after the indent.
+        let page = crate::parse_page("\tcode:notes.org\n\t * item").unwrap();
+        assert_eq!(
+            OrgGen::new(OrgGenConfig::default()).generate(&page),
+            "  #+BEGIN_SRC org\n  ,* item\n  #+END_SRC\n"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::io;
+
+/// Writer of a generator.
+///
+/// `Visit` hooks can not return errors, so the first one is kept, later
+/// writes are skipped and `finish` reports it once the page is done.
+pub(crate) struct Output<W> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> Output<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn push_str(&mut self, value: &str) {
+        if self.error.is_none() {
+            if let Err(e) = self.writer.write_all(value.as_bytes()) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    pub fn push(&mut self, value: char) {
+        self.push_str(value.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl Output<Vec<u8>> {
+    /// Takes what was written so far; generators only write `str`s.
+    pub fn take_string(&mut self) -> String {
+        String::from_utf8(std::mem::take(&mut self.writer)).expect("generators write UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parser::page,
+        visitor::markdown::{MarkdownGen, MarkdownGenConfig},
+    };
+
+    /// Accepts `limit` bytes, then fails.
+    struct Full {
+        limit: usize,
+    }
+
+    impl io::Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.limit {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "full"));
+            }
+            self.limit -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn error_test() {
+        let page = page("[a] b").unwrap().1;
+        let config = MarkdownGenConfig::default;
+
+        let mut gen = MarkdownGen::with_writer(config(), Full { limit: 4 });
+        assert_eq!(
+            gen.write(&page).unwrap_err().kind(),
+            io::ErrorKind::WriteZero
+        );
+        assert!(MarkdownGen::with_writer(config(), Full { limit: 100 })
+            .write(&page)
+            .is_ok());
+        assert_eq!(MarkdownGen::new(config()).generate(&page), "[a](a.md) b\n");
+    }
+}
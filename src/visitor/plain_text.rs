@@ -0,0 +1,166 @@
+use std::io;
+
+use crate::{
+    ast::{ExternalLink, HashTag, InternalLink, Page, Text},
+    Heading, LineKind,
+};
+
+use super::Visit;
+
+#[non_exhaustive]
+pub struct PlainTextGenConfig {
+    /// Prefix list items with `- ` and indent them by level.
+    pub bullets: bool,
+    /// Truncate the output to at most this many characters, cutting at a word boundary.
+    pub max_len: Option<usize>,
+}
+
+impl Default for PlainTextGenConfig {
+    fn default() -> Self {
+        Self {
+            bullets: true,
+            max_len: None,
+        }
+    }
+}
+
+/// Renders the visible text of a page with all markup stripped.
+///
+/// The text is trimmed and truncated as a whole, so unlike the other
+/// generators it is built in memory before being written.
+pub struct PlainTextGen<W = Vec<u8>> {
+    document: String,
+    writer: W,
+    config: PlainTextGenConfig,
+}
+
+impl PlainTextGen {
+    pub fn new(config: PlainTextGenConfig) -> Self {
+        Self::with_writer(config, Vec::new())
+    }
+
+    pub fn generate(&mut self, page: &Page) -> String {
+        self.write(page).expect("writing to a Vec can not fail");
+        String::from_utf8(std::mem::take(&mut self.writer)).expect("generators write UTF-8")
+    }
+}
+
+impl<W: io::Write> PlainTextGen<W> {
+    pub fn with_writer(config: PlainTextGenConfig, writer: W) -> Self {
+        Self {
+            document: String::new(),
+            writer,
+            config,
+        }
+    }
+
+    pub fn write(&mut self, page: &Page) -> io::Result<()> {
+        self.visit(page);
+        let document = std::mem::take(&mut self.document);
+        let text = document.trim_end();
+        match self.config.max_len {
+            Some(max_len) => self.writer.write_all(truncate(text, max_len).as_bytes())?,
+            None => self.writer.write_all(text.as_bytes())?,
+        }
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Cuts `text` to at most `max_len` characters including a trailing `…`,
+/// preferring the last whitespace before the limit so words stay intact.
+fn truncate(text: &str, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        return text.to_string();
+    }
+    if max_len == 0 {
+        return String::new();
+    }
+
+    let end = text
+        .char_indices()
+        .nth(max_len - 1)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    let head = &text[..end];
+    let head = match head.rfind(char::is_whitespace) {
+        Some(i) if i > 0 => &head[..i],
+        _ => head,
+    };
+    format!("{}…", head.trim_end())
+}
+
+impl<W> Visit for PlainTextGen<W> {
+    fn visit_page(&mut self, value: &Page) {
+        for line in value.lines.iter() {
+            if let LineKind::List(list) = &line.kind {
+                if self.config.bullets {
+                    let indent = "  ".repeat(list.level.saturating_sub(1));
+                    self.document.push_str(&format!("{}- ", indent));
+                }
+            }
+            if let Some(code) = line.code() {
+                self.document.push_str(code);
+                self.document.push('\n');
+                continue;
+            }
+            self.visit_line(line);
+            self.document.push('\n');
+        }
+    }
+
+    fn visit_hashtag(&mut self, value: &HashTag) {
+        self.document.push_str(&format!("#{}", value.value));
+    }
+
+    fn visit_bracket_internal_link(&mut self, value: &InternalLink) {
+        self.document.push_str(&value.title);
+    }
+
+    fn visit_bracket_external_link(&mut self, value: &ExternalLink) {
+        self.document
+            .push_str(value.title.as_deref().unwrap_or(&value.url));
+    }
+
+    fn visit_bracket_emphasis(&mut self, value: &crate::Emphasis) {
+        self.document.push_str(&value.text);
+    }
+
+    fn visit_bracket_heading(&mut self, value: &Heading) {
+        self.document.push_str(&value.text);
+    }
+
+    fn visit_block_quote(&mut self, value: &crate::BlockQuote) {
+        self.document.push_str(&value.value);
+    }
+
+    fn visit_text(&mut self, text: &Text) {
+        self.document.push_str(&text.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_page;
+
+    #[test]
+    fn code_block_test() {
+        let page = parse_page("run\ncode:sh\n ls\n  cd ..\nafter").unwrap();
+        assert_eq!(
+            PlainTextGen::new(PlainTextGenConfig::default()).generate(&page),
+            "run\nls\n cd ..\nafter"
+        );
+    }
+
+    #[test]
+    fn truncate_test() {
+        assert_eq!(truncate("hello world", 20), "hello world");
+        assert_eq!(truncate("hello world", 9), "hello…");
+        assert_eq!(truncate("helloworld", 5), "hell…");
+        assert_eq!(truncate("日本語のテキスト", 4), "日本語…");
+    }
+}
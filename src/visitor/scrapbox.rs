@@ -0,0 +1,249 @@
+use std::{borrow::Cow, io};
+
+use crate::{
+    ast::{ExternalLink, HashTag, InternalLink, Page, Text},
+    Heading, LineKind, ListKind,
+};
+
+use super::{output::Output, Visit};
+
+#[non_exhaustive]
+pub struct ScrapboxGenConfig {
+    /// Bold level written for H1; deeper headings get one `*` less per level.
+    pub h1_level: u8,
+}
+
+impl Default for ScrapboxGenConfig {
+    fn default() -> Self {
+        Self { h1_level: 3 }
+    }
+}
+
+/// Writes a page back as Scrapbox text, the inverse of `parse_page`.
+pub struct ScrapboxGen<W = Vec<u8>> {
+    document: Output<W>,
+    config: ScrapboxGenConfig,
+}
+
+impl ScrapboxGen {
+    pub fn new(config: ScrapboxGenConfig) -> Self {
+        Self::with_writer(config, Vec::new())
+    }
+
+    pub fn generate(&mut self, page: &Page) -> String {
+        self.write(page).expect("writing to a Vec can not fail");
+        self.document.take_string()
+    }
+}
+
+impl<W: io::Write> ScrapboxGen<W> {
+    pub fn with_writer(config: ScrapboxGenConfig, writer: W) -> Self {
+        Self {
+            document: Output::new(writer),
+            config,
+        }
+    }
+
+    pub fn write(&mut self, page: &Page) -> io::Result<()> {
+        self.visit(page);
+        self.document.finish()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.document.into_inner()
+    }
+}
+
+impl<W: io::Write> Visit for ScrapboxGen<W> {
+    fn visit_page(&mut self, value: &Page) {
+        // Item numbers of the numbered lists enclosing the current line.
+        let mut numbers: Vec<usize> = vec![];
+        for line in value.lines.iter() {
+            match &line.kind {
+                LineKind::List(list) => {
+                    let tabs = "\t".repeat(list.level);
+                    let i = list.level.saturating_sub(1);
+                    numbers.resize(i + 1, 0);
+                    match &list.kind {
+                        ListKind::Decimal => {
+                            numbers[i] += 1;
+                            let n = numbers[i];
+                            self.document.push_str(&format!("{}{}. ", tabs, n));
+                        }
+                        _ => {
+                            numbers[i] = 0;
+                            self.document.push_str(&tabs);
+                        }
+                    }
+                }
+                LineKind::CodeBlock(code) => {
+                    let tabs = "\t".repeat(code.level);
+                    numbers.truncate(code.level);
+                    self.document
+                        .push_str(&format!("{}code:{}\n", tabs, code.name));
+                    for l in line.code().unwrap_or_default().lines() {
+                        self.document.push_str(&format!("{} {}\n", tabs, l));
+                    }
+                    continue;
+                }
+                LineKind::Normal => numbers.clear(),
+            }
+            self.visit_line(line);
+            self.document.push('\n');
+        }
+    }
+
+    fn visit_hashtag(&mut self, value: &HashTag) {
+        self.document.push_str(&format!("#{}", value.value));
+    }
+
+    fn visit_bracket_internal_link(&mut self, value: &InternalLink) {
+        self.document.push_str(&format!("[{}]", value.title));
+    }
+
+    fn visit_bracket_external_link(&mut self, value: &ExternalLink) {
+        match &value.title {
+            Some(title) => self
+                .document
+                .push_str(&format!("[{} {}]", title, value.url)),
+            None => self.document.push_str(&format!("[{}]", value.url)),
+        }
+    }
+
+    fn visit_bracket_emphasis(&mut self, value: &crate::Emphasis) {
+        let tokens = format!(
+            "{}{}{}",
+            "*".repeat(value.bold as usize),
+            "/".repeat(value.italic as usize),
+            "-".repeat(value.strikethrough as usize)
+        );
+        if tokens.is_empty() {
+            self.document.push_str(&escape(&value.text));
+        } else {
+            self.document
+                .push_str(&format!("[{} {}]", tokens, value.text));
+        }
+    }
+
+    fn visit_bracket_heading(&mut self, value: &Heading) {
+        let bold = self
+            .config
+            .h1_level
+            .saturating_add(1)
+            .saturating_sub(value.level)
+            .max(1);
+        self.document
+            .push_str(&format!("[{} {}]", "*".repeat(bold as usize), value.text));
+    }
+
+    fn visit_block_quote(&mut self, value: &crate::BlockQuote) {
+        self.document.push_str(&format!("`{}`", value.value));
+    }
+
+    fn visit_text(&mut self, text: &Text) {
+        self.document.push_str(&escape(&text.value));
+    }
+}
+
+/// Wraps the words of `text` that Scrapbox would read as brackets or hashtags
+/// in backticks, so they show as written. Scrapbox has no way to escape a
+/// backtick, so words containing one are left as they are.
+fn escape(text: &str) -> Cow<'_, str> {
+    let special =
+        |word: &str| !word.contains('`') && (word.starts_with('#') || word.contains(['[', ']']));
+    if !text.split(' ').any(special) {
+        return Cow::Borrowed(text);
+    }
+    let words: Vec<_> = text
+        .split(' ')
+        .map(|word| {
+            if special(word) {
+                Cow::Owned(format!("`{}`", word))
+            } else {
+                Cow::Borrowed(word)
+            }
+        })
+        .collect();
+    Cow::Owned(words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_page, visitor::markdown::MarkdownPass, Visitor};
+
+    #[test]
+    fn round_trip_test() {
+        let text = "[*** Title]\n\
+                    see [page] and #tag with [/ italic] and `code`\n\
+                    \t[Rust https://www.rust-lang.org/]\n\
+                    \t\t1. first\n\
+                    \t\t2. second\n\
+                    code:main.rs\n \
+                    fn main() {}\n\
+                    [https://scrapbox.io]";
+        let page = parse_page(text).unwrap();
+        let generated = ScrapboxGen::new(ScrapboxGenConfig::default()).generate(&page);
+        assert_eq!(generated, format!("{}\n", text));
+    }
+
+    #[test]
+    fn code_block_test() {
+        let page = crate::parse_markdown(
+            "* item\n\n  ```sh\n  ls\n\n  cd ..\n  ```\n\nafter",
+            &Default::default(),
+        );
+        let generated = ScrapboxGen::new(ScrapboxGenConfig::default()).generate(&page);
+        assert_eq!(
+            generated,
+            "\titem\n\tcode:sh\n\t ls\n\t \n\t cd ..\n\nafter\n"
+        );
+        assert_eq!(parse_page(&generated).unwrap().lines[1], page.lines[1]);
+    }
+
+    #[test]
+    fn escape_test() {
+        let page = crate::parse_markdown(
+            "`arr[0]` is arr\\[0] or #123, not [a](a.md)",
+            &Default::default(),
+        );
+        assert_eq!(
+            ScrapboxGen::new(ScrapboxGenConfig::default()).generate(&page),
+            "`arr[0]` is `arr[0]` or `#123,` not [a]\n"
+        );
+        assert_eq!(escape("C# and a`b`"), "C# and a`b`");
+    }
+
+    #[test]
+    fn plain_emphasis_round_trip_test() {
+        let emphasis = crate::Emphasis::new("see [x] #y", 0, 0, 0);
+        let page = Page {
+            lines: vec![crate::Line::new(
+                LineKind::Normal,
+                vec![crate::Syntax::new(crate::SyntaxKind::Bracket(
+                    crate::Bracket::new(crate::BracketKind::Emphasis(emphasis)),
+                ))],
+            )],
+            ..Default::default()
+        };
+        let generated = ScrapboxGen::new(ScrapboxGenConfig::default()).generate(&page);
+        assert_eq!(generated, "see `[x]` `#y`\n");
+        let read = parse_page(&generated).unwrap();
+        assert!(read.lines[0].values.iter().all(|v| matches!(
+            v.kind,
+            crate::SyntaxKind::Text(_) | crate::SyntaxKind::BlockQuote(_)
+        )));
+    }
+
+    #[test]
+    fn heading_test() {
+        let mut page = parse_page("[*** Title]\n[** Section]").unwrap();
+        MarkdownPass::default().visit(&mut page);
+        assert_eq!(
+            ScrapboxGen::new(ScrapboxGenConfig::default()).generate(&page),
+            "[*** Title]\n[** Section]\n"
+        );
+        let config = ScrapboxGenConfig { h1_level: u8::MAX };
+        assert!(ScrapboxGen::new(config).generate(&page).starts_with("[***"));
+    }
+}
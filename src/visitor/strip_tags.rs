@@ -0,0 +1,19 @@
+use crate::ast::HashTag;
+
+use super::{TransformCommand, Visitor};
+
+/// Removes hashtags from the page, either all of them or only the listed ones.
+#[derive(Default)]
+pub struct StripTagsPass {
+    pub tags: Vec<String>,
+}
+
+impl Visitor for StripTagsPass {
+    fn visit_hashtag<'a>(&mut self, value: &HashTag<'a>) -> Option<TransformCommand<'a>> {
+        if self.tags.is_empty() || self.tags.iter().any(|tag| *tag == value.value) {
+            Some(TransformCommand::Delete)
+        } else {
+            None
+        }
+    }
+}
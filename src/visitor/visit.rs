@@ -0,0 +1,58 @@
+use crate::ast::{
+    BlockQuote, Bracket, BracketKind, Emphasis, ExternalLink, HashTag, Heading, InternalLink, Line,
+    Page, Syntax, SyntaxKind, Text,
+};
+
+/// Read-only traversal for analysis and generation.
+///
+/// Unlike `Visitor` it only borrows the page, so any number of generators can
+/// share one parsed page.
+pub trait Visit {
+    fn visit(&mut self, value: &Page) {
+        self.visit_page(value);
+    }
+
+    fn visit_page(&mut self, value: &Page) {
+        for line in value.lines.iter() {
+            self.visit_line(line);
+        }
+    }
+
+    fn visit_line(&mut self, value: &Line) {
+        for item in value.values.iter() {
+            self.visit_syntax(item);
+        }
+    }
+
+    fn visit_syntax(&mut self, value: &Syntax) {
+        match &value.kind {
+            SyntaxKind::HashTag(v) => self.visit_hashtag(v),
+            SyntaxKind::Bracket(v) => self.visit_bracket(v),
+            SyntaxKind::BlockQuote(v) => self.visit_block_quote(v),
+            SyntaxKind::Text(v) => self.visit_text(v),
+        }
+    }
+
+    fn visit_hashtag(&mut self, _value: &HashTag) {}
+
+    fn visit_bracket(&mut self, value: &Bracket) {
+        match &value.kind {
+            BracketKind::InternalLink(v) => self.visit_bracket_internal_link(v),
+            BracketKind::ExternalLink(v) => self.visit_bracket_external_link(v),
+            BracketKind::Emphasis(v) => self.visit_bracket_emphasis(v),
+            BracketKind::Heading(v) => self.visit_bracket_heading(v),
+        }
+    }
+
+    fn visit_bracket_internal_link(&mut self, _value: &InternalLink) {}
+
+    fn visit_bracket_external_link(&mut self, _value: &ExternalLink) {}
+
+    fn visit_bracket_emphasis(&mut self, _value: &Emphasis) {}
+
+    fn visit_bracket_heading(&mut self, _value: &Heading) {}
+
+    fn visit_block_quote(&mut self, _value: &BlockQuote) {}
+
+    fn visit_text(&mut self, _value: &Text) {}
+}
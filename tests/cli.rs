@@ -0,0 +1,191 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+/// A fresh directory holding the pages `a` and `b`.
+fn project(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "scrapbox-converter-cli-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("a.txt"),
+        "[*** Title]\nsee [Missing Page] and [b] #my_tag\n",
+    )
+    .unwrap();
+    fs::write(dir.join("b.txt"), "back to [a]\n").unwrap();
+    dir
+}
+
+fn run(dir: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_scrapbox-converter"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn convert_test() {
+    let dir = project("convert");
+    assert_eq!(
+        stdout(&run(&dir, &["convert", "a.txt"], "")),
+        "# Title\nsee [Missing Page](missing_page.md) and [b](b.md) [#my_tag](my_tag.md)\n\n\n"
+    );
+    assert_eq!(
+        stdout(&run(&dir, &["convert", "a.txt", "-f", "text"], "")),
+        "Title\nsee Missing Page and b #my_tag\n"
+    );
+
+    let output = run(&dir, &["convert", ".", "-o", "out"], "");
+    stdout(&output);
+    assert_eq!(
+        fs::read_to_string(dir.join("out/b.md")).unwrap(),
+        "back to [a](a.md)\n\n\n## Linked from\n* [a](a.md)\n"
+    );
+    assert!(fs::read_to_string(dir.join("out/a.md"))
+        .unwrap()
+        .contains("[b](b.md)"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stdin_test() {
+    let dir = project("stdin");
+    assert_eq!(
+        stdout(&run(&dir, &["convert", "-", "-f", "text"], "x [y]\n")),
+        "x y\n"
+    );
+    assert!(stdout(&run(&dir, &["ast", "-"], "x [y]\n")).contains("x "));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn glob_test() {
+    let dir = project("glob");
+    fs::write(dir.join("notes.md"), "# not a page\n").unwrap();
+    let output = stdout(&run(&dir, &["stats", "*.txt"], "")).to_string();
+    assert!(
+        output.starts_with("a: ") && output.contains("\nb: "),
+        "{output}"
+    );
+    assert!(output.ends_with(
+        "total: 2 pages, 5 lines (0 list items), 3 internal links, 0 external links, 1 hashtags\n"
+    ));
+
+    let error = run(&dir, &["stats", "*.png"], "");
+    assert!(!error.status.success());
+    assert!(String::from_utf8_lossy(&error.stderr).contains("no pages found for *.png"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn check_test() {
+    let dir = project("check");
+    let output = run(&dir, &["check", "."], "");
+    // A broken link fails the check.
+    assert_eq!(output.status.code(), Some(1));
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("a:2: [Missing Page]"), "{report}");
+
+    // Every line of a code block counts.
+    fs::write(
+        dir.join("a.txt"),
+        "intro\ncode:x.sh\n ls\n cd ..\n pwd\n[missing]\n",
+    )
+    .unwrap();
+    let output = run(&dir, &["check", "."], "");
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("a:6: [missing]"), "{report}");
+
+    fs::write(dir.join("a.txt"), "see [b]\n").unwrap();
+    assert!(run(&dir, &["check", "."], "").status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ast_test() {
+    let dir = project("ast");
+    let output = stdout(&run(&dir, &["ast", "b.txt"], "")).to_string();
+    assert!(output.contains("back to "), "{output}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn usage_test() {
+    let dir = project("usage");
+    let output = run(&dir, &["convert"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Usage"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stubs_test() {
+    let dir = project("stubs");
+    fs::write(
+        dir.join("c.txt"),
+        "see [a/b?], [Foo_Bar] and [foo bar] #Rust\n",
+    )
+    .unwrap();
+    let names = |out: &str| {
+        let mut names: Vec<_> = fs::read_dir(dir.join(out))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        names.sort();
+        names
+    };
+
+    stdout(&run(&dir, &["convert", ".", "-o", "plain"], ""));
+    assert_eq!(names("plain"), ["a.md", "b.md", "c.md"]);
+
+    // Every spelling of a title shares one stub, named as links point to it.
+    stdout(&run(&dir, &["convert", ".", "-o", "out", "--stubs"], ""));
+    assert_eq!(
+        names("out"),
+        [
+            "a%2Fb%3F.md",
+            "a.md",
+            "b.md",
+            "c.md",
+            "foo_bar.md",
+            "missing_page.md",
+            "my_tag.md",
+            "rust.md",
+        ]
+    );
+    let c = fs::read_to_string(dir.join("out/c.md")).unwrap();
+    assert!(c.contains("[a/b?](a%252Fb%253F.md)"), "{c}");
+    assert_eq!(
+        fs::read_to_string(dir.join("out/rust.md")).unwrap(),
+        "* [c](c.md)\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}